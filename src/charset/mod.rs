@@ -0,0 +1,447 @@
+//! Character sets of QR code data streams
+//!
+//! ECI designators in the data stream select the character set for all
+//! following segments. This module maps ECI assignment numbers to character
+//! sets and converts data in those sets to UTF-8.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::decode::{Mode, PayloadSink};
+use crate::{DeQRError, DeQRResult};
+
+mod big5;
+mod shift_jis;
+mod single_byte;
+
+/// A character set used to interpret the data of a QR code
+///
+/// Covers the character sets that can be selected by an ECI designator, as well
+/// as the ones that are detected when a code does not specify any.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CharacterSet {
+    Cp437,
+    Iso8859_1,
+    Iso8859_2,
+    Iso8859_3,
+    Iso8859_4,
+    Iso8859_5,
+    Iso8859_6,
+    Iso8859_7,
+    Iso8859_8,
+    Iso8859_9,
+    Iso8859_10,
+    Iso8859_11,
+    Iso8859_13,
+    Iso8859_14,
+    Iso8859_15,
+    Iso8859_16,
+    ShiftJis,
+    Windows1250,
+    Windows1251,
+    Windows1252,
+    Windows1256,
+    Utf16Be,
+    Utf8,
+    Ascii,
+    Big5,
+}
+
+impl CharacterSet {
+    /// Look up the character set for an ECI assignment number
+    ///
+    /// Returns `None` for assignment numbers that are unknown or not
+    /// supported.
+    pub fn from_eci(eci: u32) -> Option<Self> {
+        let charset = match eci {
+            0 | 2 => CharacterSet::Cp437,
+            1 | 3 => CharacterSet::Iso8859_1,
+            4 => CharacterSet::Iso8859_2,
+            5 => CharacterSet::Iso8859_3,
+            6 => CharacterSet::Iso8859_4,
+            7 => CharacterSet::Iso8859_5,
+            8 => CharacterSet::Iso8859_6,
+            9 => CharacterSet::Iso8859_7,
+            10 => CharacterSet::Iso8859_8,
+            11 => CharacterSet::Iso8859_9,
+            12 => CharacterSet::Iso8859_10,
+            13 => CharacterSet::Iso8859_11,
+            15 => CharacterSet::Iso8859_13,
+            16 => CharacterSet::Iso8859_14,
+            17 => CharacterSet::Iso8859_15,
+            18 => CharacterSet::Iso8859_16,
+            20 => CharacterSet::ShiftJis,
+            21 => CharacterSet::Windows1250,
+            22 => CharacterSet::Windows1251,
+            23 => CharacterSet::Windows1252,
+            24 => CharacterSet::Windows1256,
+            25 => CharacterSet::Utf16Be,
+            26 => CharacterSet::Utf8,
+            27 | 170 => CharacterSet::Ascii,
+            28 => CharacterSet::Big5,
+            _ => return None,
+        };
+        Some(charset)
+    }
+
+    /// Guess the character set of data that is not covered by an ECI designator
+    ///
+    /// The specification defaults to ISO-8859-1, but many encoders write UTF-8
+    /// or Shift JIS without saying so. Like other readers, this prefers UTF-8
+    /// (with or without BOM) if the data is valid UTF-8, then Shift JIS if the
+    /// data looks like Japanese text, and falls back to ISO-8859-1 otherwise.
+    pub fn guess(data: &[u8]) -> Self {
+        if core::str::from_utf8(data).is_ok() {
+            return CharacterSet::Utf8;
+        }
+
+        // C1 control characters are unlikely in Latin-1 text.
+        let latin1 = !data.iter().any(|b| (0x80..0xa0).contains(b));
+        // Count symbols in the upper half, which are unusual in Latin-1 text.
+        let latin1_symbols = data
+            .iter()
+            .filter(|&&b| (0xa0..0xc0).contains(&b) || b == 0xd7 || b == 0xf7)
+            .count();
+
+        if let Some(sjis) = shift_jis::Stats::collect(data) {
+            if sjis.max_katakana_run >= 3 || sjis.max_double_byte_run >= 3 {
+                return CharacterSet::ShiftJis;
+            }
+            if !latin1
+                || (sjis.max_katakana_run == 2 && sjis.katakana == 2)
+                || latin1_symbols * 10 >= data.len()
+            {
+                return CharacterSet::ShiftJis;
+            }
+        }
+        CharacterSet::Iso8859_1
+    }
+
+    /// Convert data in this character set to UTF-8, appending it to `out`
+    ///
+    /// Fails with [`DeQRError::EncodingError`] if the data contains sequences
+    /// that are invalid or unassigned in this character set.
+    pub fn decode(self, data: &[u8], out: &mut String) -> DeQRResult<()> {
+        use self::single_byte::*;
+
+        match self {
+            CharacterSet::Cp437 => decode_single_byte(data, 0x80, &CP437, out),
+            CharacterSet::Iso8859_1 => {
+                out.extend(data.iter().map(|&b| b as char));
+                Ok(())
+            }
+            CharacterSet::Iso8859_2 => decode_single_byte(data, 0xa0, &ISO_8859_2, out),
+            CharacterSet::Iso8859_3 => decode_single_byte(data, 0xa0, &ISO_8859_3, out),
+            CharacterSet::Iso8859_4 => decode_single_byte(data, 0xa0, &ISO_8859_4, out),
+            CharacterSet::Iso8859_5 => decode_single_byte(data, 0xa0, &ISO_8859_5, out),
+            CharacterSet::Iso8859_6 => decode_single_byte(data, 0xa0, &ISO_8859_6, out),
+            CharacterSet::Iso8859_7 => decode_single_byte(data, 0xa0, &ISO_8859_7, out),
+            CharacterSet::Iso8859_8 => decode_single_byte(data, 0xa0, &ISO_8859_8, out),
+            CharacterSet::Iso8859_9 => decode_single_byte(data, 0xa0, &ISO_8859_9, out),
+            CharacterSet::Iso8859_10 => decode_single_byte(data, 0xa0, &ISO_8859_10, out),
+            CharacterSet::Iso8859_11 => decode_single_byte(data, 0xa0, &ISO_8859_11, out),
+            CharacterSet::Iso8859_13 => decode_single_byte(data, 0xa0, &ISO_8859_13, out),
+            CharacterSet::Iso8859_14 => decode_single_byte(data, 0xa0, &ISO_8859_14, out),
+            CharacterSet::Iso8859_15 => decode_single_byte(data, 0xa0, &ISO_8859_15, out),
+            CharacterSet::Iso8859_16 => decode_single_byte(data, 0xa0, &ISO_8859_16, out),
+            CharacterSet::ShiftJis => shift_jis::decode(data, out),
+            CharacterSet::Windows1250 => decode_single_byte(data, 0x80, &WINDOWS_1250, out),
+            CharacterSet::Windows1251 => decode_single_byte(data, 0x80, &WINDOWS_1251, out),
+            CharacterSet::Windows1252 => decode_single_byte(data, 0x80, &WINDOWS_1252, out),
+            CharacterSet::Windows1256 => decode_single_byte(data, 0x80, &WINDOWS_1256, out),
+            CharacterSet::Utf16Be => decode_utf16_be(data, out),
+            CharacterSet::Utf8 => {
+                out.push_str(core::str::from_utf8(data).map_err(|_| DeQRError::EncodingError)?);
+                Ok(())
+            }
+            CharacterSet::Ascii => {
+                if !data.is_ascii() {
+                    return Err(DeQRError::EncodingError);
+                }
+                out.extend(data.iter().map(|&b| b as char));
+                Ok(())
+            }
+            CharacterSet::Big5 => big5::decode(data, out),
+        }
+    }
+}
+
+/// Decode a single byte character set
+///
+/// Bytes below `offset` map to the same code point, all other bytes are
+/// looked up in `upper`.
+fn decode_single_byte(data: &[u8], offset: u8, upper: &[u16], out: &mut String) -> DeQRResult<()> {
+    for &b in data {
+        let c = if b < offset {
+            Some(b as char)
+        } else {
+            match upper[(b - offset) as usize] {
+                0 => None,
+                c => char::from_u32(c as u32),
+            }
+        };
+        out.push(c.ok_or(DeQRError::EncodingError)?);
+    }
+    Ok(())
+}
+
+fn decode_utf16_be(data: &[u8], out: &mut String) -> DeQRResult<()> {
+    if data.len() % 2 != 0 {
+        return Err(DeQRError::EncodingError);
+    }
+
+    let units = data
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+    for c in char::decode_utf16(units) {
+        out.push(c.map_err(|_| DeQRError::EncodingError)?);
+    }
+    Ok(())
+}
+
+/// Text content of a QR code
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DecodedText {
+    /// The decoded content, converted to UTF-8
+    pub text: String,
+    /// The character set chosen for data not covered by an ECI designator
+    ///
+    /// This is `None` if there was no such data. The character set guessed
+    /// for data under an ECI that does not select a known one is not
+    /// reported here, so it stays `None` if all data is covered by ECIs.
+    pub charset: Option<CharacterSet>,
+}
+
+/// Collects a data stream and converts it to UTF-8
+///
+/// Byte segments are interpreted in the character set selected by the most
+/// recent ECI designator. Byte segments in front of the first designator are
+/// checked with [`CharacterSet::guess`], as are segments under an ECI that
+/// does not select a known character set, like 899 for binary data. Kanji
+/// segments are always Shift JIS, numeric and alphanumeric segments always
+/// ASCII.
+pub(crate) struct TextDecoder {
+    pieces: Vec<Piece>,
+    eci: Option<u32>,
+}
+
+enum Piece {
+    /// Text that was already converted
+    Text(String),
+    /// Bytes with the ECI that was active for them
+    Bytes(Option<u32>, Vec<u8>),
+}
+
+impl TextDecoder {
+    pub fn new() -> Self {
+        TextDecoder {
+            pieces: Vec::new(),
+            eci: None,
+        }
+    }
+
+    /// Convert the collected data and return the complete text
+    pub fn finish(self) -> DeQRResult<DecodedText> {
+        // Guess once for all data without ECI, so that it is interpreted
+        // consistently even if split over several segments.
+        let mut unspecified = Vec::new();
+        for piece in &self.pieces {
+            if let Piece::Bytes(None, data) = piece {
+                unspecified.extend_from_slice(data);
+            }
+        }
+        let guessed = match unspecified.is_empty() {
+            true => None,
+            false => Some(CharacterSet::guess(&unspecified)),
+        };
+
+        let mut text = String::new();
+        let mut first_unspecified = true;
+        for piece in &self.pieces {
+            match piece {
+                Piece::Text(t) => text.push_str(t),
+                Piece::Bytes(Some(eci), data) => CharacterSet::from_eci(*eci)
+                    .unwrap_or_else(|| CharacterSet::guess(data))
+                    .decode(data, &mut text)?,
+                Piece::Bytes(None, data) => {
+                    let charset = guessed.expect("guessed for non-empty data");
+                    let data = match (charset, first_unspecified) {
+                        (CharacterSet::Utf8, true) => data.strip_prefix(UTF8_BOM).unwrap_or(data),
+                        _ => data,
+                    };
+                    charset.decode(data, &mut text)?;
+                    first_unspecified = false;
+                }
+            }
+        }
+
+        Ok(DecodedText {
+            text,
+            charset: guessed,
+        })
+    }
+
+    fn push_text(&mut self, text: &str) {
+        match self.pieces.last_mut() {
+            Some(Piece::Text(t)) => t.push_str(text),
+            _ => self.pieces.push(Piece::Text(text.to_string())),
+        }
+    }
+}
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+impl PayloadSink for TextDecoder {
+    fn set_eci(&mut self, eci: u32) -> DeQRResult<()> {
+        self.eci = Some(eci);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, mode: Mode, data: &[u8]) -> DeQRResult<()> {
+        match mode {
+            Mode::Numeric | Mode::Alphanumeric => {
+                let text = core::str::from_utf8(data).map_err(|_| DeQRError::EncodingError)?;
+                self.push_text(text);
+            }
+            Mode::Kanji => {
+                let mut text = String::new();
+                shift_jis::decode(data, &mut text)?;
+                self.push_text(&text);
+            }
+            Mode::Byte
+            | Mode::Eci
+            | Mode::StructuredAppend
+            | Mode::Fnc1First
+            | Mode::Fnc1Second => match self.pieces.last_mut() {
+                Some(Piece::Bytes(eci, bytes)) if *eci == self.eci => bytes.extend_from_slice(data),
+                _ => self.pieces.push(Piece::Bytes(self.eci, data.to_vec())),
+            },
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(eci: u32, data: &[u8]) -> DeQRResult<String> {
+        let mut out = String::new();
+        CharacterSet::from_eci(eci)
+            .ok_or(DeQRError::EncodingError)?
+            .decode(data, &mut out)?;
+        Ok(out)
+    }
+
+    #[test]
+    fn test_single_byte() {
+        assert_eq!(decode(3, b"caf\xe9").unwrap(), "café");
+        assert_eq!(decode(4, b"\xb1\xe6").unwrap(), "ąć");
+        assert_eq!(decode(7, b"\xbf\xe0\xd8\xd2\xd5\xe2").unwrap(), "Привет");
+        assert_eq!(decode(17, b"\xa4").unwrap(), "€");
+        assert_eq!(decode(23, b"\x80 \x93a\x94").unwrap(), "€ “a”");
+        assert_eq!(decode(2, b"\x81").unwrap(), "ü");
+        assert_eq!(decode(23, b"\x81"), Err(DeQRError::EncodingError));
+    }
+
+    #[test]
+    fn test_shift_jis() {
+        assert_eq!(decode(20, b"\x93\xfa\x96\x7b").unwrap(), "日本");
+        assert_eq!(decode(20, b"ABC\xb1").unwrap(), "ABCｱ");
+        assert_eq!(decode(20, b"\x93"), Err(DeQRError::EncodingError));
+    }
+
+    #[test]
+    fn test_utf16_be() {
+        assert_eq!(decode(25, b"\x00h\x00i\xd8\x3d\xde\x00").unwrap(), "hi😀");
+        assert_eq!(decode(25, b"\x00h\x00"), Err(DeQRError::EncodingError));
+        assert_eq!(decode(25, b"\xd8\x3d"), Err(DeQRError::EncodingError));
+    }
+
+    #[test]
+    fn test_big5() {
+        assert_eq!(decode(28, b"\xa4\xa4\xa4\xe5").unwrap(), "中文");
+        assert_eq!(decode(28, b"\xa4"), Err(DeQRError::EncodingError));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        assert_eq!(CharacterSet::from_eci(29), None);
+        assert_eq!(CharacterSet::from_eci(899), None);
+    }
+
+    #[test]
+    fn test_guess() {
+        assert_eq!(CharacterSet::guess(b"plain"), CharacterSet::Utf8);
+        assert_eq!(CharacterSet::guess("Grüße".as_bytes()), CharacterSet::Utf8);
+        assert_eq!(
+            CharacterSet::guess(b"\xef\xbb\xbfGr\xc3\xbc\xc3\x9fe"),
+            CharacterSet::Utf8
+        );
+        assert_eq!(CharacterSet::guess(b"Gr\xfc\xdfe"), CharacterSet::Iso8859_1);
+        assert_eq!(CharacterSet::guess(b"caf\xe9"), CharacterSet::Iso8859_1);
+        assert_eq!(
+            CharacterSet::guess(b"\x93\xfa\x96\x7b\x8c\xea"),
+            CharacterSet::ShiftJis
+        );
+        assert_eq!(CharacterSet::guess(b"\xb1\xb2\xb3"), CharacterSet::ShiftJis);
+    }
+
+    #[test]
+    fn test_text_decoder_switches_charset() {
+        let mut dec = TextDecoder::new();
+        dec.write_bytes(Mode::Byte, b"plain ").unwrap();
+        dec.set_eci(3).unwrap();
+        dec.write_bytes(Mode::Byte, b"\xe9").unwrap();
+        dec.set_eci(20).unwrap();
+        dec.write_bytes(Mode::Byte, b"\x93\xfa").unwrap();
+        let decoded = dec.finish().unwrap();
+        assert_eq!(decoded.text, "plain é日");
+        assert_eq!(decoded.charset, Some(CharacterSet::Utf8));
+    }
+
+    #[test]
+    fn test_text_decoder_defaults() {
+        let mut dec = TextDecoder::new();
+        dec.write_bytes(Mode::Byte, b"Stra\xdfe").unwrap();
+        let decoded = dec.finish().unwrap();
+        assert_eq!(decoded.text, "Straße");
+        assert_eq!(decoded.charset, Some(CharacterSet::Iso8859_1));
+
+        let mut dec = TextDecoder::new();
+        dec.write_bytes(Mode::Byte, b"\xef\xbb\xbfok").unwrap();
+        assert_eq!(dec.finish().unwrap().text, "ok");
+
+        let mut dec = TextDecoder::new();
+        dec.set_eci(26).unwrap();
+        dec.write_bytes(Mode::Byte, b"ok").unwrap();
+        assert_eq!(dec.finish().unwrap().charset, None);
+    }
+
+    #[test]
+    fn test_text_decoder_unknown_eci() {
+        // Data under ECIs without a known character set is guessed
+        let mut dec = TextDecoder::new();
+        dec.set_eci(899).unwrap();
+        dec.write_bytes(Mode::Byte, "Grüße ".as_bytes()).unwrap();
+        dec.set_eci(30).unwrap();
+        dec.write_bytes(Mode::Byte, b"caf\xe9").unwrap();
+        let decoded = dec.finish().unwrap();
+        assert_eq!(decoded.text, "Grüße café");
+        assert_eq!(decoded.charset, None);
+    }
+
+    #[test]
+    fn test_text_decoder_guesses_all_segments() {
+        // The first segment alone would be valid UTF-8, but all segments
+        // without ECI are interpreted in the same character set
+        let mut dec = TextDecoder::new();
+        dec.write_bytes(Mode::Byte, b"\xc3\xa9").unwrap();
+        dec.write_bytes(Mode::Numeric, b"1").unwrap();
+        dec.write_bytes(Mode::Kanji, b"\x93\xfa").unwrap();
+        dec.write_bytes(Mode::Byte, b"\xe9").unwrap();
+        let decoded = dec.finish().unwrap();
+        assert_eq!(decoded.text, "Ã©1日é");
+        assert_eq!(decoded.charset, Some(CharacterSet::Iso8859_1));
+    }
+}
@@ -1,17 +1,25 @@
-use std::io::Write;
-use std::mem;
-
-use g2p::{g2p, GaloisField};
-
-use crate::version_db::{RSParameters, VERSION_DATA_BASE};
-use crate::{BitGrid, DeQRError, DeQRResult};
-
-g2p!(GF16, 4, modulus: 0b1_0011);
-g2p!(GF256, 8, modulus: 0b1_0001_1101);
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{cmp, mem};
+
+use crate::galois::{GaloisField, GF16, GF256};
+use crate::structured_append::StructuredAppend;
+use crate::version_db::{
+    RSParameters, VersionInfo, MICRO_VERSION_DATA_BASE, MODEL1_VERSION_DATA_BASE, RMQR_SIZES,
+    RMQR_VERSION_DATA_BASE, VERSION_DATA_BASE,
+};
+use crate::{BitGrid, ByteWrite, DeQRError, DeQRResult};
 
 pub const MAX_PAYLOAD_SIZE: usize = 8896;
 
 /// Version of a QR Code which determines its size
+///
+/// Model 1 codes share the sizes of regular QR codes, up to version 14. For
+/// Micro QR codes, versions 1 to 4 stand for M1 to M4. For rectangular Micro
+/// QR codes, versions 1 to 32 stand for R7x43 to R17x139, ordered by height
+/// first and width second.
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub struct Version(pub usize);
 
@@ -31,20 +39,193 @@ impl Version {
     pub fn to_size(&self) -> usize {
         self.0 * 4 + 17
     }
+
+    /// Given the grid size of a Micro QR code, determine its version
+    pub fn from_micro_size(b: usize) -> DeQRResult<Self> {
+        match b {
+            11 | 13 | 15 | 17 => Ok(Version((b - 9) / 2)),
+            _ => Err(DeQRError::InvalidVersion),
+        }
+    }
+
+    /// Return the size of a Micro QR grid of the given version
+    pub fn to_micro_size(&self) -> usize {
+        self.0 * 2 + 9
+    }
+
+    /// Given the grid width and height of an rMQR code, determine its version
+    pub fn from_rmqr_size(width: usize, height: usize) -> DeQRResult<Self> {
+        RMQR_SIZES
+            .iter()
+            .skip(1)
+            .position(|&size| size == (width, height))
+            .map(|idx| Version(idx + 1))
+            .ok_or(DeQRError::InvalidVersion)
+    }
+
+    /// Return the width and height of an rMQR grid of the given version
+    pub fn to_rmqr_size(&self) -> (usize, usize) {
+        RMQR_SIZES[self.0]
+    }
+}
+
+/// The kind of symbol a grid contains
+///
+/// New kinds of symbols may be added in minor releases, so matches need a
+/// wildcard arm.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SymbolKind {
+    /// A regular QR code, with three finder patterns
+    Qr,
+    /// A QR code of the obsolete Model 1, with three finder patterns but no
+    /// alignment patterns
+    Model1,
+    /// A Micro QR code, with a single finder pattern
+    Micro,
+    /// A rectangular Micro QR code (rMQR), with a finder pattern on the left
+    /// and a sub-finder pattern in the bottom right corner
+    Rmqr,
 }
 
 /// MetaData for a QR grid
 ///
 /// Stores information about the size/version of given grid. Also contains
 /// information about the error correction level and bit mask used.
-#[derive(Debug, Clone, Copy)]
+///
+/// New fields may be added in minor releases, so it can only be created by
+/// this crate.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct MetaData {
+    /// Whether the grid is a regular, a Model 1, a Micro or a rectangular
+    /// Micro QR code
+    pub kind: SymbolKind,
     /// The version/size of the grid
     pub version: Version,
     /// the error correction leven, between 0 and 3
+    ///
+    /// M1 Micro QR codes only detect errors, they report level 1 (L). rMQR
+    /// codes only use level 0 (M) and 2 (H).
     pub ecc_level: u16,
     /// The mask that was used, value between 0 and 7
+    ///
+    /// Micro QR codes only use masks 0 to 3. rMQR codes always use mask 4.
     pub mask: u16,
+    /// The ECI assignment numbers found in the data stream, in order
+    ///
+    /// Only filled in when the payload was decoded.
+    pub eci: Vec<u32>,
+    /// Position of the symbol in a Structured Append sequence, if any
+    ///
+    /// Only filled in when the payload was decoded.
+    pub structured_append: Option<StructuredAppend>,
+    /// The FNC1 mode of the data stream, if any
+    ///
+    /// Only filled in when the payload was decoded.
+    pub fnc1: Option<Fnc1>,
+    /// Error correction statistics of each block, in the order the blocks
+    /// are interleaved
+    ///
+    /// Only filled in when the data was error corrected.
+    pub ecc_blocks: Vec<BlockStats>,
+    /// Whether the code is mirrored, i.e. its modules are transposed
+    ///
+    /// Mirrored codes are not allowed by the specification, but are produced
+    /// by some generators, and seen through the back of transparent media.
+    pub mirrored: bool,
+}
+
+impl MetaData {
+    /// The AIM symbology identifier for the decoded data
+    ///
+    /// The identifier tells whether ECIs were used and whether the data
+    /// follows the GS1 or another industry format, e.g. `"]Q3"` for GS1 data.
+    /// Model 1 codes are always identified as `"]Q0"`.
+    pub fn symbology_identifier(&self) -> &'static str {
+        if self.kind == SymbolKind::Model1 {
+            return "]Q0";
+        }
+        match (self.eci.is_empty(), self.fnc1) {
+            (true, None) => "]Q1",
+            (false, None) => "]Q2",
+            (true, Some(Fnc1::First)) => "]Q3",
+            (false, Some(Fnc1::First)) => "]Q4",
+            (true, Some(Fnc1::Second(_))) => "]Q5",
+            (false, Some(Fnc1::Second(_))) => "]Q6",
+        }
+    }
+
+    /// The fraction of error correction capacity left unused, between 0 and 1
+    ///
+    /// As in print quality grading, this is the figure of the block with the
+    /// most errors. A value of 1 means no errors were found at all, a value
+    /// close to 0 means the symbol was barely readable. Returns `None` if the
+    /// data was not error corrected.
+    pub fn unused_error_correction(&self) -> Option<f64> {
+        self.ecc_blocks
+            .iter()
+            .map(|block| block.unused() as f64 / block.ecc_codewords as f64)
+            .min_by(|a, b| a.total_cmp(b))
+    }
+}
+
+/// Error correction statistics of a single block
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BlockStats {
+    /// Number of data codewords in the block
+    pub data_codewords: usize,
+    /// Number of error correction codewords in the block
+    pub ecc_codewords: usize,
+    /// Number of codewords that were wrong and had to be corrected
+    pub corrected: usize,
+    /// Number of error correction codewords used up by the correction
+    ///
+    /// Each error takes up two of them. Erasures take up only one, whether
+    /// they turned out to be wrong or not.
+    pub used: usize,
+}
+
+impl BlockStats {
+    /// Number of error correction codewords left unused
+    pub fn unused(&self) -> usize {
+        self.ecc_codewords - self.used
+    }
+
+    /// Number of additional errors the block could have corrected
+    pub fn remaining_errors(&self) -> usize {
+        self.unused() / 2
+    }
+}
+
+/// FNC1 mode of a data stream
+///
+/// FNC1 marks data formatted according to a specific industry standard. In
+/// FNC1 mode, `%` in alphanumeric segments is decoded as the GS (0x1d)
+/// separator, while `%%` stands for a literal `%`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Fnc1 {
+    /// FNC1 in first position, the data consists of GS1 element strings
+    First,
+    /// FNC1 in second position, the data follows an industry specification
+    /// identified by the contained AIM application indicator
+    ///
+    /// The indicator is stored as encoded: 0 to 99 stand for two digits,
+    /// 165 to 190 and 197 to 222 for a letter in ASCII plus 100.
+    Second(u8),
+}
+
+impl Fnc1 {
+    /// The AIM application indicator, as transmitted in front of the data
+    ///
+    /// Returns `None` for FNC1 in first position.
+    pub fn application_indicator(&self) -> Option<String> {
+        match *self {
+            Fnc1::First => None,
+            Fnc1::Second(v @ 0..=99) => Some(format!("{:02}", v)),
+            Fnc1::Second(v) => Some(char::from(v - 100).to_string()),
+        }
+    }
 }
 
 /// The bit stream contained in the QR Code
@@ -54,9 +235,19 @@ pub struct RawData {
     pub data: [u8; MAX_PAYLOAD_SIZE],
     /// Length of the bit stream in bits.
     pub len: usize,
+    /// Codewords containing uncertain bits, one bit per codeword
+    erasures: [u8; MAX_PAYLOAD_SIZE / 8],
 }
 
 impl RawData {
+    fn new() -> Self {
+        RawData {
+            data: [0; MAX_PAYLOAD_SIZE],
+            len: 0,
+            erasures: [0; MAX_PAYLOAD_SIZE / 8],
+        }
+    }
+
     /// Push a new bit into the bit stream
     pub fn push(&mut self, bit: bool) {
         assert!((self.len / 8) < MAX_PAYLOAD_SIZE);
@@ -68,16 +259,108 @@ impl RawData {
         }
         self.len += 1;
     }
+
+    /// Push a bit that could not be read reliably
+    ///
+    /// The codeword containing the bit is marked as erasure.
+    pub fn push_uncertain(&mut self, bit: bool) {
+        let codeword = self.len >> 3;
+        self.erasures[codeword >> 3] |= 1 << (codeword & 7);
+        self.push(bit);
+    }
+
+    /// Whether the codeword at the given index contains uncertain bits
+    pub fn is_erasure(&self, codeword: usize) -> bool {
+        self.erasures[codeword >> 3] & (1 << (codeword & 7)) != 0
+    }
+}
+
+/// Size of the scratch buffer needed to decode any symbol
+///
+/// See [`Grid::scratch_size`](crate::Grid::scratch_size) for the size needed
+/// by a specific grid.
+pub const MAX_SCRATCH_SIZE: usize = scratch_len(VERSION_DATA_BASE[40].data_bytes);
+
+/// Size of the scratch buffer needed for a symbol with the given number of
+/// codewords
+///
+/// The buffer holds the corrected data, the codewords as read and one
+/// erasure flag per codeword.
+const fn scratch_len(data_bytes: usize) -> usize {
+    2 * data_bytes + data_bytes.div_ceil(8)
+}
+
+/// Size of the scratch buffer needed to decode a grid of the given size
+///
+/// Covers all kinds of symbols the grid can be read as, also mirrored.
+/// Returns 0 if no symbol has that size.
+pub(crate) fn scratch_size(width: usize, height: usize) -> usize {
+    let mut infos: [Option<&VersionInfo>; 3] = [None; 3];
+    if width != height {
+        // The grid may be an rMQR code read mirrored
+        infos[0] = Version::from_rmqr_size(width, height)
+            .or_else(|_| Version::from_rmqr_size(height, width))
+            .ok()
+            .map(|v| &RMQR_VERSION_DATA_BASE[v.0]);
+    } else if let Ok(v) = Version::from_micro_size(width) {
+        infos[0] = Some(&MICRO_VERSION_DATA_BASE[v.0]);
+    } else if let Ok(v) = Version::from_size(width) {
+        infos[0] = Some(&VERSION_DATA_BASE[v.0]);
+        infos[1] = MODEL1_VERSION_DATA_BASE.get(v.0);
+    }
+    infos
+        .iter()
+        .flatten()
+        .map(|info| scratch_len(info.data_bytes))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Buffer to read and correct the codewords of a symbol in
+///
+/// The corrected data is kept at the start of the buffer, so that it can be
+/// read as [`CorrectedDataStream`] once correction is done.
+pub(crate) struct Scratch<'a> {
+    buf: &'a mut [u8],
+    /// Whether block statistics and ECI designators are collected in the
+    /// metadata, which needs allocation
+    collect: bool,
+}
+
+impl<'a> Scratch<'a> {
+    pub(crate) fn new(buf: &'a mut [u8], collect: bool) -> Self {
+        Scratch { buf, collect }
+    }
+
+    /// Split the buffer into the corrected data, the codewords as read and
+    /// their erasure flags
+    fn split(&mut self, data_bytes: usize) -> DeQRResult<(&mut [u8], &mut [u8], &mut [u8])> {
+        if self.buf.len() < scratch_len(data_bytes) {
+            return Err(DeQRError::BufferTooSmall);
+        }
+        let (out, rest) = self.buf.split_at_mut(data_bytes);
+        let (raw, rest) = rest.split_at_mut(data_bytes);
+        Ok((out, raw, &mut rest[..data_bytes.div_ceil(8)]))
+    }
+
+    /// The corrected data, once correction is done
+    fn stream(&self, bit_len: usize) -> CorrectedDataStream<'_> {
+        CorrectedDataStream {
+            data: &self.buf[..bit_len.div_ceil(8)],
+            ptr: 0,
+            bit_len,
+        }
+    }
 }
 
 #[derive(Clone)]
-pub struct CorrectedDataStream {
-    data: [u8; MAX_PAYLOAD_SIZE],
+pub struct CorrectedDataStream<'a> {
+    data: &'a [u8],
     ptr: usize,
     bit_len: usize,
 }
 
-impl CorrectedDataStream {
+impl CorrectedDataStream<'_> {
     pub fn bits_remaining(&self) -> usize {
         assert!(self.bit_len >= self.ptr);
         self.bit_len - self.ptr
@@ -85,7 +368,7 @@ impl CorrectedDataStream {
 
     pub fn take_bits(&mut self, nbits: usize) -> usize {
         let mut ret = 0;
-        let max_len = ::std::cmp::min(self.bits_remaining(), nbits);
+        let max_len = cmp::min(self.bits_remaining(), nbits);
         assert!(max_len <= mem::size_of::<usize>() * 8);
         for _ in 0..max_len {
             let b = self.data[self.ptr >> 3];
@@ -101,6 +384,309 @@ impl CorrectedDataStream {
     }
 }
 
+/// Encoding mode of a segment in the data stream
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Mode {
+    /// Decimal digits, decoded as ASCII
+    Numeric,
+    /// Digits, upper case letters and some symbols, decoded as ASCII
+    Alphanumeric,
+    /// Arbitrary bytes, interpreted according to the active ECI
+    Byte,
+    /// Double byte characters, decoded as Shift JIS
+    Kanji,
+    /// ECI designator, selects the character set of the following segments
+    Eci,
+    /// Structured Append header, marks the symbol as part of a sequence
+    StructuredAppend,
+    /// FNC1 in first position, marks GS1 formatted data
+    Fnc1First,
+    /// FNC1 in second position, marks data formatted to an industry
+    /// specification
+    Fnc1Second,
+}
+
+impl Mode {
+    /// Number of bits holding `count` characters, without the segment header
+    pub(crate) fn data_bits(self, count: usize) -> usize {
+        match self {
+            Mode::Numeric => 10 * (count / 3) + [0, 4, 7][count % 3],
+            Mode::Alphanumeric => 11 * (count / 2) + 6 * (count % 2),
+            Mode::Byte => 8 * count,
+            Mode::Kanji => 13 * count,
+            Mode::Eci | Mode::StructuredAppend | Mode::Fnc1First | Mode::Fnc1Second => 0,
+        }
+    }
+
+    /// Length of the character count indicator for a given symbol
+    pub(crate) fn count_bits(self, meta: &MetaData) -> usize {
+        if meta.kind == SymbolKind::Micro {
+            let lengths = match self {
+                Mode::Numeric => [3, 4, 5, 6],
+                Mode::Alphanumeric => [0, 3, 4, 5],
+                Mode::Byte => [0, 0, 4, 5],
+                Mode::Kanji => [0, 0, 3, 4],
+                _ => [0, 0, 0, 0],
+            };
+            return lengths[meta.version.0 - 1];
+        }
+        if meta.kind == SymbolKind::Rmqr {
+            let lengths: &[usize; 32] = match self {
+                Mode::Numeric => &[
+                    4, 5, 6, 7, 7, 5, 6, 7, 7, 8, 4, 6, 7, 7, 8, 8, 5, 6, 7, 7, 8, 8, 7, 7, 8, 8,
+                    9, 7, 8, 8, 8, 9,
+                ],
+                Mode::Alphanumeric => &[
+                    3, 5, 5, 6, 6, 5, 5, 6, 6, 7, 4, 5, 6, 6, 7, 7, 5, 6, 6, 7, 7, 8, 6, 7, 7, 7,
+                    8, 6, 7, 7, 8, 8,
+                ],
+                Mode::Byte => &[
+                    3, 4, 5, 5, 6, 4, 5, 5, 6, 6, 3, 5, 5, 6, 6, 7, 4, 5, 6, 6, 7, 7, 6, 6, 7, 7,
+                    7, 6, 6, 7, 7, 8,
+                ],
+                Mode::Kanji => &[
+                    2, 3, 4, 5, 5, 3, 4, 5, 5, 6, 2, 4, 5, 5, 6, 6, 3, 5, 5, 6, 6, 7, 5, 5, 6, 6,
+                    7, 5, 6, 6, 6, 7,
+                ],
+                _ => return 0,
+            };
+            return lengths[meta.version.0 - 1];
+        }
+
+        let lengths = match self {
+            Mode::Numeric => [10, 12, 14],
+            Mode::Alphanumeric => [9, 11, 13],
+            Mode::Byte => [8, 16, 16],
+            Mode::Kanji => [8, 10, 12],
+            Mode::Eci | Mode::StructuredAppend | Mode::Fnc1First | Mode::Fnc1Second => [0, 0, 0],
+        };
+        match meta.version {
+            Version(0..=9) => lengths[0],
+            Version(10..=26) => lengths[1],
+            _ => lengths[2],
+        }
+    }
+}
+
+/// A single segment of the data stream
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Segment {
+    /// The encoding mode
+    pub mode: Mode,
+    /// The number of characters, as given by the character count indicator
+    ///
+    /// ECI, Structured Append and FNC1 segments have no characters, so this
+    /// is always 0 for them.
+    pub count: usize,
+    /// Offset of the mode indicator in the error corrected bit stream
+    pub bit_offset: usize,
+    /// The active ECI assignment number, if any
+    ///
+    /// For ECI segments, this is the newly designated ECI.
+    pub eci: Option<u32>,
+    /// The decoded bytes, as they would be written by `decode_to`
+    pub data: Vec<u8>,
+}
+
+/// Error in the data stream of a symbol, see [`PartialDecode`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PayloadError {
+    /// The error that stopped decoding
+    pub error: DeQRError,
+    /// Offset of the mode indicator of the segment that failed, in the error
+    /// corrected bit stream
+    pub bit_offset: usize,
+}
+
+impl From<PayloadError> for DeQRError {
+    fn from(err: PayloadError) -> Self {
+        err.error
+    }
+}
+
+/// Result of decoding a symbol as far as possible, see
+/// [`Grid::decode_partial`](crate::Grid::decode_partial)
+#[derive(Debug, Clone)]
+pub struct PartialDecode {
+    /// Metadata of the symbol, with the ECI designators, Structured Append
+    /// header and FNC1 mode found before the error
+    pub meta: MetaData,
+    /// All segments decoded completely before the error, or all segments if
+    /// there was none
+    pub segments: Vec<Segment>,
+    /// The error that stopped decoding, if any
+    pub error: Option<PayloadError>,
+}
+
+/// Deviation of a symbol from ISO/IEC 18004, see
+/// [`Grid::validate`](crate::Grid::validate)
+///
+/// Unless noted otherwise, offsets are given in the error corrected bit
+/// stream, like [`Segment::bit_offset`]. New kinds of violations may be
+/// added in minor releases, so matches need a wildcard arm.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Violation {
+    /// The data ends without terminator
+    ///
+    /// The terminator may only be cut short by the end of the data, and its
+    /// remaining bits must still be zero.
+    MissingTerminator {
+        /// Offset of the bits in place of the terminator
+        bit_offset: usize,
+    },
+    /// The bits between the terminator and the next codeword are not zero
+    PadBits {
+        /// Offset of the first bit after the terminator
+        bit_offset: usize,
+    },
+    /// A pad codeword is not the expected alternating 0xEC and 0x11
+    ///
+    /// In M1 and M3 Micro QR codes, the last, 4 bit long data word has to be
+    /// zero instead.
+    PadCodeword {
+        /// Offset of the pad codeword
+        bit_offset: usize,
+        /// The value that was found
+        found: u8,
+        /// The value required at this position
+        expected: u8,
+    },
+    /// A remainder bit after the last codeword is not zero
+    RemainderBit {
+        /// Row of the module holding the bit, as in [`BitGrid::bit`]
+        y: usize,
+        /// Column of the module holding the bit, as in [`BitGrid::bit`]
+        x: usize,
+    },
+    /// A group of digits in numeric mode has a value with more digits than
+    /// the group holds, e.g. 1000 for three digits
+    NumericOverflow {
+        /// Offset of the group
+        bit_offset: usize,
+        /// The value of the group
+        value: usize,
+    },
+    /// A pair of characters in alphanumeric mode has a value of 45 * 45 or
+    /// more, or a single character a value of 45 or more
+    AlphanumericOverflow {
+        /// Offset of the pair or character
+        bit_offset: usize,
+        /// The value of the pair or character
+        value: usize,
+    },
+    /// The character count of a segment needs more data than is left in the
+    /// symbol
+    CountOverflow {
+        /// Offset of the mode indicator of the segment
+        bit_offset: usize,
+        /// The character count
+        count: usize,
+    },
+    /// The data could not be decoded any further
+    Payload(PayloadError),
+}
+
+/// Destination for the content of a data stream
+///
+/// Segments are passed on as raw bytes. Whenever an ECI designator is found,
+/// the sink is told so it can interpret the following bytes accordingly.
+pub(crate) trait PayloadSink {
+    /// Start a new segment at the given bit offset
+    fn begin_segment(&mut self, _mode: Mode, _count: usize, _bit_offset: usize) -> DeQRResult<()> {
+        Ok(())
+    }
+
+    /// Select the ECI for all following data
+    fn set_eci(&mut self, eci: u32) -> DeQRResult<()>;
+
+    /// Append decoded data of a segment with the given mode
+    fn write_bytes(&mut self, mode: Mode, data: &[u8]) -> DeQRResult<()>;
+
+    /// Note a deviation from the standard, which does not stop decoding
+    fn violation(&mut self, _violation: Violation) {}
+}
+
+/// Sink collecting all segments of a data stream
+pub(crate) struct SegmentCollector {
+    pub segments: Vec<Segment>,
+    eci: Option<u32>,
+}
+
+impl SegmentCollector {
+    pub fn new() -> Self {
+        SegmentCollector {
+            segments: Vec::new(),
+            eci: None,
+        }
+    }
+}
+
+impl PayloadSink for SegmentCollector {
+    fn begin_segment(&mut self, mode: Mode, count: usize, bit_offset: usize) -> DeQRResult<()> {
+        self.segments.push(Segment {
+            mode,
+            count,
+            bit_offset,
+            eci: self.eci,
+            data: Vec::new(),
+        });
+        Ok(())
+    }
+
+    fn set_eci(&mut self, eci: u32) -> DeQRResult<()> {
+        self.eci = Some(eci);
+        if let Some(segment) = self.segments.last_mut() {
+            segment.eci = Some(eci);
+        }
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, _mode: Mode, data: &[u8]) -> DeQRResult<()> {
+        let segment = self
+            .segments
+            .last_mut()
+            .expect("data is always written after a segment starts");
+        segment.data.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+/// Sink collecting deviations from the standard, ignoring the content
+pub(crate) struct ViolationCollector {
+    pub violations: Vec<Violation>,
+}
+
+impl PayloadSink for ViolationCollector {
+    fn set_eci(&mut self, _eci: u32) -> DeQRResult<()> {
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, _mode: Mode, _data: &[u8]) -> DeQRResult<()> {
+        Ok(())
+    }
+
+    fn violation(&mut self, violation: Violation) {
+        self.violations.push(violation);
+    }
+}
+
+/// Sink writing the raw bytes of all segments to a writer
+struct WriterSink<W>(W);
+
+impl<W> PayloadSink for WriterSink<W>
+where
+    W: ByteWrite,
+{
+    fn set_eci(&mut self, _eci: u32) -> DeQRResult<()> {
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, _mode: Mode, data: &[u8]) -> DeQRResult<()> {
+        self.0.write_all(data)
+    }
+}
+
 /// Given a grid try to decode and write it to the output writer
 ///
 /// This tries to read the bit patterns from a [Grid](trait.Grid.html), correct
@@ -108,88 +694,802 @@ impl CorrectedDataStream {
 /// also returns [MetaData](struct.MetaData.html) of the read grid.
 pub fn decode<W>(code: &dyn BitGrid, writer: W) -> DeQRResult<MetaData>
 where
-    W: Write,
+    W: ByteWrite,
+{
+    decode_into(code, &mut WriterSink(writer))
+}
+
+/// Given a grid try to decode and write it to the output writer, without
+/// allocating
+///
+/// All data is read and corrected in the scratch buffer. Block statistics and
+/// ECI designators are not collected in the metadata.
+pub(crate) fn decode_with_scratch<W>(
+    code: &dyn BitGrid,
+    scratch: &mut [u8],
+    writer: W,
+) -> DeQRResult<MetaData>
+where
+    W: ByteWrite,
 {
-    fn _decode(c: &dyn BitGrid) -> DeQRResult<(MetaData, CorrectedDataStream)> {
-        let (meta, raw) = get_raw(c, true)?;
-        let stream = codestream_ecc(&meta, raw)?;
-        Ok((meta, stream))
-    }
-    let (meta, stream) = match _decode(code) {
-        Ok((meta, stream)) => (meta, stream),
-        Err(original) => match _decode(&crate::MirroredGrid(code)) {
-            Ok((meta, stream)) => (meta, stream),
-            Err(_) => return Err(original),
-        },
+    decode_in(
+        code,
+        &mut Scratch::new(scratch, false),
+        &mut WriterSink(writer),
+    )
+}
+
+/// Given a grid try to decode it, passing the content to the given sink
+pub(crate) fn decode_into<S>(code: &dyn BitGrid, sink: &mut S) -> DeQRResult<MetaData>
+where
+    S: PayloadSink,
+{
+    let mut buf = vec![0; scratch_size(code.width(), code.height())];
+    decode_in(code, &mut Scratch::new(&mut buf, true), sink)
+}
+
+/// Given a grid try to decode its segments, keeping those decoded before an
+/// error in the data stream
+///
+/// Only errors in reading and correcting the data fail the whole decode.
+pub(crate) fn decode_partial(code: &dyn BitGrid) -> DeQRResult<PartialDecode> {
+    let mut buf = vec![0; scratch_size(code.width(), code.height())];
+    let mut scratch = Scratch::new(&mut buf, true);
+    let (mut meta, bit_len, _) = read_corrected(code, &mut scratch)?;
+
+    let mut collector = SegmentCollector::new();
+    let error = decode_payload(&mut meta, scratch.stream(bit_len), &mut collector, true).err();
+    let mut segments = collector.segments;
+    if let Some(error) = error {
+        segments.retain(|segment| segment.bit_offset < error.bit_offset);
+    }
+    Ok(PartialDecode {
+        meta,
+        segments,
+        error,
+    })
+}
+
+/// Given a grid try to decode it, and list every deviation from the standard
+///
+/// Only errors in reading and correcting the data fail. An error in the data
+/// stream ends the list.
+pub(crate) fn validate(code: &dyn BitGrid) -> DeQRResult<(MetaData, Vec<Violation>)> {
+    let mut buf = vec![0; scratch_size(code.width(), code.height())];
+    let mut scratch = Scratch::new(&mut buf, true);
+    let (mut meta, bit_len, is_mirrored) = read_corrected(code, &mut scratch)?;
+
+    let mut collector = ViolationCollector {
+        violations: Vec::new(),
+    };
+    if let Err(err) = decode_payload(&mut meta, scratch.stream(bit_len), &mut collector, true) {
+        // A count running past the data makes the segment underflow
+        let explained = matches!(
+            collector.violations.last(),
+            Some(Violation::CountOverflow { bit_offset, .. }) if *bit_offset == err.bit_offset
+        );
+        if !explained || err.error != DeQRError::DataUnderflow {
+            collector.violations.push(Violation::Payload(err));
+        }
+    }
+
+    let mirrored = crate::MirroredGrid(code);
+    let grid: &dyn BitGrid = if is_mirrored { &mirrored } else { code };
+    let data_bits = version_info(&meta).data_bytes * 8;
+    walk_data(grid, &meta, |bit, y, x| {
+        if bit >= data_bits && read_bit(grid, &meta, y, x, true) {
+            let (y, x) = if is_mirrored { (x, y) } else { (y, x) };
+            collector.violations.push(Violation::RemainderBit { y, x });
+        }
+    });
+    Ok((meta, collector.violations))
+}
+
+/// Decode a grid in the given scratch buffer, passing the content to the sink
+fn decode_in<S>(code: &dyn BitGrid, scratch: &mut Scratch, sink: &mut S) -> DeQRResult<MetaData>
+where
+    S: PayloadSink,
+{
+    let (mut meta, bit_len, _) = read_corrected(code, scratch)?;
+    decode_payload(&mut meta, scratch.stream(bit_len), sink, scratch.collect)?;
+    Ok(meta)
+}
+
+/// Read the format and data of a grid, and correct errors in the data
+///
+/// If the grid can not be read as it is, it is tried mirrored. Model 1 codes
+/// are only tried once all other attempts failed, and a damaged format is
+/// only recovered if no reading has a valid one. Returns the length of the
+/// corrected data in bits, and whether the grid was read mirrored.
+fn read_corrected(
+    code: &dyn BitGrid,
+    scratch: &mut Scratch,
+) -> DeQRResult<(MetaData, usize, bool)> {
+    fn _decode(
+        c: &dyn BitGrid,
+        model1: bool,
+        recover: bool,
+        scratch: &mut Scratch,
+    ) -> DeQRResult<(MetaData, usize)> {
+        let meta = if model1 {
+            read_model1_format(c)
+        } else {
+            read_format(c)
+        };
+        match meta {
+            Ok(meta) => {
+                correct_data(c, meta, scratch).map(|(meta, corrected)| (meta, corrected.bit_len))
+            }
+            Err(DeQRError::FormatEcc) if recover && model1 => {
+                recover_format(c, SymbolKind::Model1, scratch)
+            }
+            Err(DeQRError::FormatEcc) if recover && c.width() == c.height() && c.size() >= 21 => {
+                recover_format(c, SymbolKind::Qr, scratch)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    let mirrored = crate::MirroredGrid(code);
+    let readings: [(&dyn BitGrid, bool, bool); 4] = [
+        (code, false, false),
+        (&mirrored, false, true),
+        (code, true, false),
+        (&mirrored, true, true),
+    ];
+    let found = |(mut meta, bit_len): (MetaData, usize), is_mirrored: bool| {
+        meta.mirrored = is_mirrored;
+        (meta, bit_len, is_mirrored)
+    };
+
+    // Brute forcing the format is slow and may pick the wrong one, so it is
+    // only done once no reading has a valid format
+    let mut original = None;
+    let mut unreadable = [false; 4];
+    for (i, &(grid, model1, is_mirrored)) in readings.iter().enumerate() {
+        match _decode(grid, model1, false, scratch) {
+            Ok(decoded) => return Ok(found(decoded, is_mirrored)),
+            Err(err) => {
+                unreadable[i] = err == DeQRError::FormatEcc;
+                original.get_or_insert(err);
+            }
+        }
+    }
+    for (i, &(grid, model1, is_mirrored)) in readings.iter().enumerate() {
+        if !unreadable[i] {
+            continue;
+        }
+        match _decode(grid, model1, true, scratch) {
+            Ok(decoded) => return Ok(found(decoded, is_mirrored)),
+            Err(err) if i == 0 => original = Some(err),
+            Err(_) => {}
+        }
+    }
+    Err(original.unwrap_or(DeQRError::FormatEcc))
+}
+
+/// Outcome of correcting the data of a symbol
+struct Corrected {
+    /// Length of the corrected data in bits
+    bit_len: usize,
+    /// Number of codewords that had to be corrected
+    codewords: usize,
+}
+
+/// Read the data of a grid with known format, and correct errors
+fn correct_data(
+    code: &dyn BitGrid,
+    mut meta: MetaData,
+    scratch: &mut Scratch,
+) -> DeQRResult<(MetaData, Corrected)> {
+    let corrected = correct_codewords(code, &mut meta, scratch, |_| ())?;
+    Ok((meta, corrected))
+}
+
+/// Read the data of a grid into the scratch buffer and correct it
+///
+/// The index of every codeword that had to be corrected is passed to
+/// `corrected`, counting in the order the codewords were read.
+fn correct_codewords<F>(
+    code: &dyn BitGrid,
+    meta: &mut MetaData,
+    scratch: &mut Scratch,
+    corrected: F,
+) -> DeQRResult<Corrected>
+where
+    F: FnMut(usize),
+{
+    let collect = scratch.collect;
+    let (out, raw, erasures) = scratch.split(version_info(meta).data_bytes)?;
+    read_data(code, meta, true, raw, erasures);
+    codestream_ecc(meta, raw, erasures, out, collect, corrected)
+}
+
+/// Most errors in a copy of the format information for which the format is
+/// still recovered, one more than can be corrected
+const MAX_FORMAT_ERRORS: u32 = 4;
+
+/// Decode a regular or Model 1 QR code whose format information is damaged
+///
+/// There are only 32 combinations of error correction level and mask. The
+/// ones closest to both copies of the format information are tried, keeping
+/// the one whose data needs the fewest corrections.
+///
+/// Combinations with more than [`MAX_FORMAT_ERRORS`] errors in a copy are not
+/// tried, so grids without a code are rejected early. This does not apply to
+/// wiped copies, with all modules of the same color, whose errors say nothing
+/// about how far off the format is.
+fn recover_format(
+    code: &dyn BitGrid,
+    kind: SymbolKind,
+    scratch: &mut Scratch,
+) -> DeQRResult<(MetaData, usize)> {
+    let copies = read_format_copies(code, kind);
+    let read = copies.map(|copy| !matches!(copy ^ format_mask(kind), 0 | 0x7fff));
+    let errors = |fdata: u16| {
+        let word = bch_15_5(fdata);
+        copies.map(|copy| (word ^ copy).count_ones())
+    };
+    let candidates = || {
+        (0..32).filter(|&fdata| {
+            let errors = errors(fdata);
+            (0..2).all(|i| !read[i] || errors[i] <= MAX_FORMAT_ERRORS)
+        })
+    };
+    let distance = |fdata: u16| errors(fdata).iter().sum::<u32>();
+    let nearest = candidates()
+        .map(distance)
+        .min()
+        .ok_or(DeQRError::FormatEcc)?;
+
+    try_formats(
+        code,
+        kind,
+        candidates().filter(|&fdata| distance(fdata) == nearest),
+        scratch,
+    )
+}
+
+/// Decode a regular or Model 1 QR code with each of the given format data
+///
+/// Keeps the result whose data needs the fewest corrections, and returns the
+/// length of its corrected data in bits. Fails with [`DeQRError::FormatEcc`]
+/// if none of them can be decoded.
+fn try_formats<I>(
+    code: &dyn BitGrid,
+    kind: SymbolKind,
+    candidates: I,
+    scratch: &mut Scratch,
+) -> DeQRResult<(MetaData, usize)>
+where
+    I: IntoIterator<Item = u16>,
+{
+    let mut best: Option<(usize, u16)> = None;
+    let mut last = None;
+    for fdata in candidates {
+        let meta = qr_metadata(code, kind, fdata)?;
+        last = None;
+        if let Ok((meta, corrected)) = correct_data(code, meta, scratch) {
+            if best.is_none_or(|(fewest, _)| corrected.codewords < fewest) {
+                best = Some((corrected.codewords, fdata));
+            }
+            last = Some((fdata, meta, corrected.bit_len));
+        }
+    }
+    let (_, fdata) = best.ok_or(DeQRError::FormatEcc)?;
+
+    // The scratch buffer holds the data of the last candidate, so the best
+    // one has to be corrected again unless they are the same
+    match last {
+        Some((last, meta, bit_len)) if last == fdata => Ok((meta, bit_len)),
+        _ => {
+            let meta = qr_metadata(code, kind, fdata)?;
+            let (meta, corrected) = correct_data(code, meta, scratch)?;
+            Ok((meta, corrected.bit_len))
+        }
+    }
+}
+
+/// Known properties of a symbol, used instead of reading them from the grid
+///
+/// All properties are optional. If both the error correction level and the
+/// mask are known, the format information is not read at all. Otherwise, the
+/// known properties override those read from the format information. Should
+/// that be unreadable, all remaining combinations are tried.
+///
+/// A complete hint can be created from the [`MetaData`] of a previous symbol.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct DecodeHints {
+    /// The kind of symbol
+    ///
+    /// If unknown, the kind is guessed from the shape of the grid, assuming
+    /// regular QR codes for square grids of 21 modules or more.
+    pub kind: Option<SymbolKind>,
+    /// The version of the symbol
+    ///
+    /// Grids of a different size are re-sampled at the size of this version,
+    /// if possible, see [`BitGrid::resample`].
+    pub version: Option<Version>,
+    /// The error correction level, between 0 and 3
+    pub ecc_level: Option<u16>,
+    /// The mask, between 0 and 7
+    pub mask: Option<u16>,
+}
+
+impl From<&MetaData> for DecodeHints {
+    fn from(meta: &MetaData) -> Self {
+        DecodeHints {
+            kind: Some(meta.kind),
+            version: Some(meta.version),
+            ecc_level: Some(meta.ecc_level),
+            mask: Some(meta.mask),
+        }
+    }
+}
+
+/// Given a grid and hints about its properties try to decode it, passing the
+/// content to the given sink
+pub(crate) fn decode_with_hints_into<S>(
+    code: &dyn BitGrid,
+    hints: &DecodeHints,
+    sink: &mut S,
+) -> DeQRResult<MetaData>
+where
+    S: PayloadSink,
+{
+    let kind = hints.kind.unwrap_or(if code.width() != code.height() {
+        SymbolKind::Rmqr
+    } else if code.size() < 21 {
+        SymbolKind::Micro
+    } else {
+        SymbolKind::Qr
+    });
+
+    // Only square codes have a size that is measured and may be wrong
+    let resampled = match hints.version {
+        Some(version) if matches!(kind, SymbolKind::Qr | SymbolKind::Model1) => {
+            let size = version.to_size();
+            if code.size() != size {
+                code.resample(size)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    let code = resampled.as_deref().unwrap_or(code);
+
+    let mut buf = vec![0; scratch_size(code.width(), code.height())];
+    let mut scratch = Scratch::new(&mut buf, true);
+    let mirrored = crate::MirroredGrid(code);
+    let (mut meta, bit_len) = match read_hinted(code, kind, hints, &mut scratch) {
+        Ok(read) => read,
+        Err(err) => {
+            let (mut meta, bit_len) =
+                read_hinted(&mirrored, kind, hints, &mut scratch).map_err(|_| err)?;
+            meta.mirrored = true;
+            (meta, bit_len)
+        }
+    };
+    decode_payload(&mut meta, scratch.stream(bit_len), sink, true)?;
+    Ok(meta)
+}
+
+/// Read and correct the data of a grid of known kind, using the hints
+fn read_hinted(
+    code: &dyn BitGrid,
+    kind: SymbolKind,
+    hints: &DecodeHints,
+    scratch: &mut Scratch,
+) -> DeQRResult<(MetaData, usize)> {
+    let version = match kind {
+        SymbolKind::Qr | SymbolKind::Model1 if code.width() == code.height() => {
+            Version::from_size(code.size())?
+        }
+        SymbolKind::Micro if code.width() == code.height() => {
+            Version::from_micro_size(code.size())?
+        }
+        SymbolKind::Rmqr => Version::from_rmqr_size(code.width(), code.height())?,
+        _ => return Err(DeQRError::InvalidGridSize),
+    };
+    if hints.version.is_some_and(|hint| hint != version) {
+        return Err(DeQRError::InvalidVersion);
+    }
+
+    if let (Some(ecc_level), Some(mask)) = (hints.ecc_level, hints.mask) {
+        let meta = symbol_metadata(kind, version, ecc_level, mask)?;
+        let (meta, corrected) = correct_data(code, meta, scratch)?;
+        return Ok((meta, corrected.bit_len));
+    }
+
+    let read = match kind {
+        SymbolKind::Qr => read_qr_format(code, kind),
+        SymbolKind::Model1 => read_model1_format(code),
+        SymbolKind::Micro => read_micro_format(code),
+        SymbolKind::Rmqr => read_rmqr_format(code),
+    };
+    match read {
+        Ok(meta) => {
+            let ecc_level = hints.ecc_level.unwrap_or(meta.ecc_level);
+            let mask = hints.mask.unwrap_or(meta.mask);
+            let meta = symbol_metadata(kind, meta.version, ecc_level, mask)?;
+            let (meta, corrected) = correct_data(code, meta, scratch)?;
+            Ok((meta, corrected.bit_len))
+        }
+        Err(DeQRError::FormatEcc) if matches!(kind, SymbolKind::Qr | SymbolKind::Model1) => {
+            let matches_hints = |fdata: &u16| {
+                hints
+                    .ecc_level
+                    .is_none_or(|ecc_level| fdata >> 3 == ecc_level)
+                    && hints.mask.is_none_or(|mask| fdata & 7 == mask)
+            };
+            try_formats(code, kind, (0..32).filter(matches_hints), scratch)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Create the metadata of a symbol from its properties
+///
+/// Fails if the symbol can not have the given properties.
+pub(crate) fn symbol_metadata(
+    kind: SymbolKind,
+    version: Version,
+    ecc_level: u16,
+    mask: u16,
+) -> DeQRResult<MetaData> {
+    let (versions, valid_mask) = match kind {
+        SymbolKind::Qr => (VERSION_DATA_BASE.len(), mask < 8),
+        SymbolKind::Model1 => (MODEL1_VERSION_DATA_BASE.len(), mask < 8),
+        SymbolKind::Micro => (MICRO_VERSION_DATA_BASE.len(), mask < 4),
+        SymbolKind::Rmqr => (RMQR_VERSION_DATA_BASE.len(), mask == 4),
     };
+    if version.0 == 0 || version.0 >= versions {
+        return Err(DeQRError::InvalidVersion);
+    }
+    if ecc_level >= 4 || !valid_mask {
+        return Err(DeQRError::FormatEcc);
+    }
 
-    decode_payload(&meta, stream, writer)?;
+    let meta = MetaData {
+        kind,
+        version,
+        ecc_level,
+        mask,
+        eci: Vec::new(),
+        structured_append: None,
+        fnc1: None,
+        ecc_blocks: Vec::new(),
+        mirrored: false,
+    };
+    let ecc = &version_info(&meta).ecc[ecc_level as usize];
+    if ecc.bs <= ecc.dw {
+        return Err(DeQRError::FormatEcc);
+    }
     Ok(meta)
 }
 
+/// A codeword that had to be corrected, along with the modules it was read
+/// from
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CorrectedCodeword {
+    /// Index of the codeword in the order the codewords are read, see
+    /// [`RawData`]
+    pub index: usize,
+    /// Coordinates of the modules holding the bits of the codeword, as
+    /// `(y, x)` like in [`BitGrid::bit`]
+    ///
+    /// The first module holds the most significant bit. The last data word
+    /// of M1 and M3 Micro QR codes only has 4 modules.
+    pub modules: Vec<(usize, usize)>,
+}
+
+/// Locate all codewords that had to be corrected while decoding a grid
+pub(crate) fn error_locations(
+    code: &dyn BitGrid,
+) -> DeQRResult<(MetaData, Vec<CorrectedCodeword>)> {
+    let mut buf = vec![0; scratch_size(code.width(), code.height())];
+    let mut scratch = Scratch::new(&mut buf, true);
+    let (mut meta, _, is_mirrored) = read_corrected(code, &mut scratch)?;
+
+    // Correct the data once more, but this time take note of the corrections
+    let mirrored = crate::MirroredGrid(code);
+    let grid: &dyn BitGrid = if is_mirrored { &mirrored } else { code };
+    meta.ecc_blocks.clear();
+    let mut locations = Vec::new();
+    correct_codewords(grid, &mut meta, &mut scratch, |index| {
+        locations.push(CorrectedCodeword {
+            index,
+            modules: Vec::new(),
+        })
+    })?;
+    locations.sort_by_key(|location| location.index);
+
+    walk_data(grid, &meta, |bit, y, x| {
+        if let Ok(i) = locations.binary_search_by_key(&(bit / 8), |location| location.index) {
+            let module = if is_mirrored { (x, y) } else { (y, x) };
+            locations[i].modules.push(module);
+        }
+    });
+    Ok((meta, locations))
+}
+
 /// Return extracted metadata and the raw, uncorrected bit stream.
 ///
 /// Optionally, you can keep the stream masked, so the data appears as it was in the image.
+///
+/// Grids are read as Model 1 codes if their format information only matches
+/// that of a Model 1 code.
 pub fn get_raw(code: &dyn BitGrid, remove_masked: bool) -> DeQRResult<(MetaData, RawData)> {
-    let meta = read_format(code)?;
-    let raw = read_data(code, &meta, remove_masked);
+    let meta = read_format(code).or_else(|err| read_model1_format(code).map_err(|_| err))?;
+    let mut raw = RawData::new();
+    raw.len = read_data(code, &meta, remove_masked, &mut raw.data, &mut raw.erasures);
     Ok((meta, raw))
 }
 
-fn decode_payload<W>(meta: &MetaData, mut ds: CorrectedDataStream, mut writer: W) -> DeQRResult<()>
+/// Decode the segments of the corrected data, passing them to the sink
+///
+/// ECI designators are only collected in the metadata if `collect` is set.
+/// On failure, all segments before the one that failed have been passed to
+/// the sink.
+fn decode_payload<S>(
+    meta: &mut MetaData,
+    mut ds: CorrectedDataStream,
+    sink: &mut S,
+    collect: bool,
+) -> Result<(), PayloadError>
 where
-    W: Write,
+    S: PayloadSink,
 {
-    while ds.bits_remaining() >= 4 {
-        let ty = ds.take_bits(4);
-        match ty {
-            0 => break,
-            1 => decode_numeric(meta, &mut ds, &mut writer),
-            2 => decode_alpha(meta, &mut ds, &mut writer),
-            4 => decode_byte(meta, &mut ds, &mut writer),
-            8 => decode_kanji(meta, &mut ds, &mut writer),
-            7 => decode_eci(meta, &mut ds, &mut writer),
-            _ => Err(DeQRError::UnknownDataType)?,
-        }?;
+    // The top half of the first codeword of Model 1 codes is not used
+    if meta.kind == SymbolKind::Model1 {
+        ds.take_bits(4);
+    }
+
+    loop {
+        let bit_offset = ds.ptr;
+        match decode_segment(meta, &mut ds, sink, collect) {
+            Ok(true) => (),
+            Ok(false) => {
+                check_padding(meta, &ds, bit_offset, sink);
+                return Ok(());
+            }
+            Err(error) => return Err(PayloadError { error, bit_offset }),
+        }
     }
-    Ok(())
 }
 
-fn decode_eci<W>(_meta: &MetaData, ds: &mut CorrectedDataStream, mut _writer: W) -> DeQRResult<()>
+/// Decode the next segment, passing it to the sink
+///
+/// Returns `false` once the end of the data is reached.
+fn decode_segment<S>(
+    meta: &mut MetaData,
+    ds: &mut CorrectedDataStream,
+    sink: &mut S,
+    collect: bool,
+) -> DeQRResult<bool>
 where
-    W: Write,
+    S: PayloadSink,
 {
+    let bit_offset = ds.ptr;
+    let mode = match read_mode(meta, ds)? {
+        Some(mode) => mode,
+        None => return Ok(false),
+    };
+
+    if mode == Mode::Eci {
+        let eci = decode_eci(ds)?;
+        if collect {
+            meta.eci.push(eci);
+        }
+        sink.begin_segment(mode, 0, bit_offset)?;
+        sink.set_eci(eci)?;
+        return Ok(true);
+    }
+    if mode == Mode::StructuredAppend {
+        meta.structured_append = Some(decode_structured_append(ds)?);
+        sink.begin_segment(mode, 0, bit_offset)?;
+        return Ok(true);
+    }
+    if mode == Mode::Fnc1First {
+        meta.fnc1 = Some(Fnc1::First);
+        sink.begin_segment(mode, 0, bit_offset)?;
+        return Ok(true);
+    }
+    if mode == Mode::Fnc1Second {
+        meta.fnc1 = Some(Fnc1::Second(decode_application_indicator(ds)?));
+        sink.begin_segment(mode, 0, bit_offset)?;
+        return Ok(true);
+    }
+
+    let count = ds.take_bits(mode.count_bits(meta));
+    if mode.data_bits(count) > ds.bits_remaining() {
+        sink.violation(Violation::CountOverflow { bit_offset, count });
+    }
+    sink.begin_segment(mode, count, bit_offset)?;
+    match mode {
+        Mode::Numeric => decode_numeric(count, ds, sink),
+        Mode::Alphanumeric => decode_alpha(count, ds, sink, meta.fnc1.is_some()),
+        Mode::Byte => decode_byte(count, ds, sink),
+        Mode::Kanji => decode_kanji(count, ds, sink),
+        Mode::Eci | Mode::StructuredAppend | Mode::Fnc1First | Mode::Fnc1Second => {
+            unreachable!("handled above")
+        }
+    }?;
+    Ok(true)
+}
+
+/// Check the terminator and the padding following it
+///
+/// The terminator starts at `start`, the offset at which no further mode was
+/// read.
+fn check_padding<S>(meta: &MetaData, ds: &CorrectedDataStream, start: usize, sink: &mut S)
+where
+    S: PayloadSink,
+{
+    let end = ds.bit_len;
+    let bits = |offset: usize, nbits: usize| {
+        let mut ds = ds.clone();
+        ds.ptr = offset;
+        ds.take_bits(nbits)
+    };
+
+    let terminator = match meta.kind {
+        SymbolKind::Qr | SymbolKind::Model1 => 4,
+        SymbolKind::Micro => meta.version.0 * 2 + 1,
+        SymbolKind::Rmqr => 3,
+    };
+    let terminator_end = cmp::min(start + terminator, end);
+    if bits(start, terminator_end - start) != 0 {
+        sink.violation(Violation::MissingTerminator { bit_offset: start });
+    }
+
+    let boundary = cmp::min(terminator_end.next_multiple_of(8), end);
+    if bits(terminator_end, boundary - terminator_end) != 0 {
+        sink.violation(Violation::PadBits {
+            bit_offset: terminator_end,
+        });
+    }
+
+    /* Pad codewords alternate, except for the short last data word of M1
+     * and M3 codes which is zero */
+    for (i, offset) in (boundary..end).step_by(8).enumerate() {
+        let nbits = cmp::min(8, end - offset);
+        let expected = match nbits {
+            8 => [0xec, 0x11][i % 2],
+            _ => 0,
+        };
+        let found = bits(offset, nbits) as u8;
+        if found != expected {
+            sink.violation(Violation::PadCodeword {
+                bit_offset: offset,
+                found,
+                expected,
+            });
+        }
+    }
+}
+
+/// Read the next mode indicator
+///
+/// Returns `None` once the terminator or the end of the data is reached.
+fn read_mode(meta: &MetaData, ds: &mut CorrectedDataStream) -> DeQRResult<Option<Mode>> {
+    if meta.kind == SymbolKind::Micro {
+        // The terminator is 3, 5, 7 or 9 bits long and may be cut short at
+        // the end of the data. As an all-zero mode indicator selects numeric
+        // mode, look ahead for the complete terminator instead.
+        let terminator = meta.version.0 * 2 + 1;
+        if ds.bits_remaining() < terminator {
+            return Ok(None);
+        }
+        let start = ds.ptr;
+        if ds.take_bits(terminator) == 0 {
+            return Ok(None);
+        }
+        ds.ptr = start;
+
+        let mode = match ds.take_bits(meta.version.0 - 1) {
+            0 => Mode::Numeric,
+            1 => Mode::Alphanumeric,
+            2 => Mode::Byte,
+            3 => Mode::Kanji,
+            _ => Err(DeQRError::UnknownDataType)?,
+        };
+        return Ok(Some(mode));
+    }
+    if meta.kind == SymbolKind::Rmqr {
+        if ds.bits_remaining() < 3 {
+            return Ok(None);
+        }
+        let mode = match ds.take_bits(3) {
+            0 => return Ok(None),
+            1 => Mode::Numeric,
+            2 => Mode::Alphanumeric,
+            3 => Mode::Byte,
+            4 => Mode::Kanji,
+            5 => Mode::Fnc1First,
+            6 => Mode::Fnc1Second,
+            7 => Mode::Eci,
+            _ => unreachable!("mode indicator is 3 bits"),
+        };
+        return Ok(Some(mode));
+    }
+
+    if ds.bits_remaining() < 4 {
+        return Ok(None);
+    }
+    let mode = match ds.take_bits(4) {
+        0 => return Ok(None),
+        1 => Mode::Numeric,
+        2 => Mode::Alphanumeric,
+        4 => Mode::Byte,
+        8 => Mode::Kanji,
+        7 => Mode::Eci,
+        3 => Mode::StructuredAppend,
+        5 => Mode::Fnc1First,
+        9 => Mode::Fnc1Second,
+        _ => Err(DeQRError::UnknownDataType)?,
+    };
+    Ok(Some(mode))
+}
+
+/// Read the Structured Append header following its mode indicator
+fn decode_structured_append(ds: &mut CorrectedDataStream) -> DeQRResult<StructuredAppend> {
+    if ds.bits_remaining() < 16 {
+        Err(DeQRError::DataUnderflow)?
+    }
+
+    let index = ds.take_bits(4) as u8;
+    let total = ds.take_bits(4) as u8 + 1;
+    let parity = ds.take_bits(8) as u8;
+    Ok(StructuredAppend {
+        index,
+        total,
+        parity,
+    })
+}
+
+/// Read the AIM application indicator following an FNC1 second position
+/// mode indicator
+fn decode_application_indicator(ds: &mut CorrectedDataStream) -> DeQRResult<u8> {
     if ds.bits_remaining() < 8 {
         Err(DeQRError::DataUnderflow)?
     }
 
-    let mut _eci = ds.take_bits(8) as u32;
-    if _eci & 0xc0 == 0x80 {
+    match ds.take_bits(8) as u8 {
+        v @ (0..=99 | 165..=190 | 197..=222) => Ok(v),
+        _ => Err(DeQRError::UnknownDataType),
+    }
+}
+
+/// Read the ECI assignment number following an ECI mode indicator
+fn decode_eci(ds: &mut CorrectedDataStream) -> DeQRResult<u32> {
+    if ds.bits_remaining() < 8 {
+        Err(DeQRError::DataUnderflow)?
+    }
+
+    let first = ds.take_bits(8) as u32;
+    if first & 0x80 == 0 {
+        Ok(first)
+    } else if first & 0xc0 == 0x80 {
         if ds.bits_remaining() < 8 {
             Err(DeQRError::DataUnderflow)?
         }
-        _eci = (_eci << 8) | (ds.take_bits(8) as u32)
-    } else if _eci & 0xe0 == 0xc0 {
+        Ok(((first & 0x3f) << 8) | (ds.take_bits(8) as u32))
+    } else if first & 0xe0 == 0xc0 {
         if ds.bits_remaining() < 16 {
             Err(DeQRError::DataUnderflow)?
         }
-
-        _eci = (_eci << 16) | (ds.take_bits(16) as u32)
+        Ok(((first & 0x1f) << 16) | (ds.take_bits(16) as u32))
+    } else {
+        Err(DeQRError::UnknownDataType)
     }
-    Ok(())
 }
 
-fn decode_kanji<W>(meta: &MetaData, ds: &mut CorrectedDataStream, mut writer: W) -> DeQRResult<()>
+fn decode_kanji<S>(count: usize, ds: &mut CorrectedDataStream, sink: &mut S) -> DeQRResult<()>
 where
-    W: Write,
+    S: PayloadSink,
 {
-    let nbits = match meta.version {
-        Version(0..=9) => 8,
-        Version(10..=26) => 10,
-        _ => 12,
-    };
-
-    let count = ds.take_bits(nbits);
     if ds.bits_remaining() < count * 13 {
         Err(DeQRError::DataUnderflow)?
     }
@@ -205,74 +1505,122 @@ where
         } else {
             (intermediate + 0xc140) as u16
         };
-        writer
-            .write_all(&[(sjw >> 8) as u8, (sjw & 0xff) as u8])
-            .map_err(|_| DeQRError::IoError)?;
+        sink.write_bytes(Mode::Kanji, &[(sjw >> 8) as u8, (sjw & 0xff) as u8])?;
     }
     Ok(())
 }
 
-fn decode_byte<W>(meta: &MetaData, ds: &mut CorrectedDataStream, mut writer: W) -> DeQRResult<()>
+fn decode_byte<S>(count: usize, ds: &mut CorrectedDataStream, sink: &mut S) -> DeQRResult<()>
 where
-    W: Write,
+    S: PayloadSink,
 {
-    let nbits = match meta.version {
-        Version(0..=9) => 8,
-        _ => 16,
-    };
-
-    let count = ds.take_bits(nbits);
     if ds.bits_remaining() < count * 8 {
         Err(DeQRError::DataUnderflow)?;
     }
 
     for _ in 0..count {
         let buf = &[ds.take_bits(8) as u8];
-        writer.write_all(buf).map_err(|_| DeQRError::IoError)?;
+        sink.write_bytes(Mode::Byte, buf)?;
     }
     Ok(())
 }
 
-fn decode_alpha<W>(meta: &MetaData, ds: &mut CorrectedDataStream, mut writer: W) -> DeQRResult<()>
+fn decode_alpha<S>(
+    count: usize,
+    ds: &mut CorrectedDataStream,
+    sink: &mut S,
+    fnc1: bool,
+) -> DeQRResult<()>
 where
-    W: Write,
+    S: PayloadSink,
 {
-    let nbits = match meta.version {
-        Version(0..=9) => 9,
-        Version(10..=26) => 11,
-        _ => 13,
-    };
-    let mut count = ds.take_bits(nbits);
+    let mut count = count;
     let mut buf = [0; 2];
+    let mut percent = false;
 
     while count >= 2 {
-        alpha_tuple(&mut buf, ds, 11, 2)?;
-        writer.write_all(&buf[..]).map_err(|_| DeQRError::IoError)?;
+        alpha_tuple(&mut buf, ds, 11, 2, sink)?;
+        write_alpha(&buf[..], fnc1, &mut percent, sink)?;
         count -= 2;
     }
 
     if count == 1 {
-        alpha_tuple(&mut buf, ds, 6, 1)?;
-        writer
-            .write_all(&buf[..1])
-            .map_err(|_| DeQRError::IoError)?;
+        alpha_tuple(&mut buf, ds, 6, 1, sink)?;
+        write_alpha(&buf[..1], fnc1, &mut percent, sink)?;
+    }
+
+    if percent {
+        sink.write_bytes(Mode::Alphanumeric, &[GS])?;
+    }
+
+    Ok(())
+}
+
+/// The group separator, which FNC1 mode encodes as `%` in alphanumeric mode
+const GS: u8 = 0x1d;
+
+/// Write alphanumeric characters, translating `%` to GS in FNC1 mode
+///
+/// As `%%` may span two tuples, a pending `%` is carried over in `percent`.
+fn write_alpha<S>(chars: &[u8], fnc1: bool, percent: &mut bool, sink: &mut S) -> DeQRResult<()>
+where
+    S: PayloadSink,
+{
+    if !fnc1 {
+        return sink.write_bytes(Mode::Alphanumeric, chars);
+    }
+
+    let mut out = [0; 3];
+    let mut len = 0;
+    for &c in chars {
+        if mem::take(percent) {
+            if c == b'%' {
+                out[len] = b'%';
+                len += 1;
+                continue;
+            }
+            out[len] = GS;
+            len += 1;
+        }
+        if c == b'%' {
+            *percent = true;
+        } else {
+            out[len] = c;
+            len += 1;
+        }
     }
 
+    if len > 0 {
+        sink.write_bytes(Mode::Alphanumeric, &out[..len])?;
+    }
     Ok(())
 }
 
-fn alpha_tuple(
+/// Characters of the alphanumeric mode, in the order of their values
+pub(crate) const ALPHA_MAP: &[u8; 45] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+fn alpha_tuple<S>(
     buf: &mut [u8; 2],
     ds: &mut CorrectedDataStream,
     nbits: usize,
     digits: usize,
-) -> DeQRResult<()> {
+    sink: &mut S,
+) -> DeQRResult<()>
+where
+    S: PayloadSink,
+{
     if ds.bits_remaining() < nbits {
         Err(DeQRError::DataUnderflow)
     } else {
+        let bit_offset = ds.ptr;
         let mut tuple = ds.take_bits(nbits);
+        if tuple >= 45usize.pow(digits as u32) {
+            sink.violation(Violation::AlphanumericOverflow {
+                bit_offset,
+                value: tuple,
+            });
+        }
         for i in (0..digits).rev() {
-            const ALPHA_MAP: &[u8; 46] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:\x00";
             buf[i] = ALPHA_MAP[tuple % 45];
             tuple /= 45;
         }
@@ -280,51 +1628,52 @@ fn alpha_tuple(
     }
 }
 
-fn decode_numeric<W>(meta: &MetaData, ds: &mut CorrectedDataStream, mut writer: W) -> DeQRResult<()>
+fn decode_numeric<S>(count: usize, ds: &mut CorrectedDataStream, sink: &mut S) -> DeQRResult<()>
 where
-    W: Write,
+    S: PayloadSink,
 {
-    let nbits = match meta.version {
-        Version(0..=9) => 10,
-        Version(10..=26) => 12,
-        _ => 14,
-    };
-
-    let mut count = ds.take_bits(nbits);
+    let mut count = count;
     let mut buf = [0; 3];
     while count >= 3 {
-        numeric_tuple(&mut buf, ds, 10, 3)?;
-        writer.write_all(&buf[..]).map_err(|_| DeQRError::IoError)?;
+        numeric_tuple(&mut buf, ds, 10, 3, sink)?;
+        sink.write_bytes(Mode::Numeric, &buf[..])?;
         count -= 3;
     }
 
     if count == 2 {
-        numeric_tuple(&mut buf, ds, 7, 2)?;
-        writer
-            .write_all(&buf[..2])
-            .map_err(|_| DeQRError::IoError)?;
+        numeric_tuple(&mut buf, ds, 7, 2, sink)?;
+        sink.write_bytes(Mode::Numeric, &buf[..2])?;
         count -= 2;
     }
     if count == 1 {
-        numeric_tuple(&mut buf, ds, 4, 1)?;
-        writer
-            .write_all(&buf[..1])
-            .map_err(|_| DeQRError::IoError)?;
+        numeric_tuple(&mut buf, ds, 4, 1, sink)?;
+        sink.write_bytes(Mode::Numeric, &buf[..1])?;
     }
 
     Ok(())
 }
 
-fn numeric_tuple(
+fn numeric_tuple<S>(
     buf: &mut [u8; 3],
     ds: &mut CorrectedDataStream,
     nbits: usize,
     digits: usize,
-) -> DeQRResult<()> {
+    sink: &mut S,
+) -> DeQRResult<()>
+where
+    S: PayloadSink,
+{
     if ds.bits_remaining() < nbits {
         Err(DeQRError::DataUnderflow)
     } else {
+        let bit_offset = ds.ptr;
         let mut tuple = ds.take_bits(nbits);
+        if tuple >= 10usize.pow(digits as u32) {
+            sink.violation(Violation::NumericOverflow {
+                bit_offset,
+                value: tuple,
+            });
+        }
         for i in (0..digits).rev() {
             buf[i] = (tuple % 10) as u8 + b'0';
             tuple /= 10;
@@ -333,14 +1682,24 @@ fn numeric_tuple(
     }
 }
 
-fn codestream_ecc(meta: &MetaData, ds: RawData) -> DeQRResult<CorrectedDataStream> {
-    let mut out = CorrectedDataStream {
-        data: [0; MAX_PAYLOAD_SIZE],
-        ptr: 0,
-        bit_len: 0,
-    };
-
-    let ver = &VERSION_DATA_BASE[meta.version.0];
+/// Deinterleave and correct the blocks of the codewords as read
+///
+/// The corrected data words are written to `out`, one block after the other.
+/// Statistics of every block are collected in the metadata if `collect` is
+/// set. The index of every codeword that had to be corrected is passed to
+/// `corrected`, counting in the order the codewords were read.
+fn codestream_ecc<F>(
+    meta: &mut MetaData,
+    raw: &[u8],
+    erasures: &[u8],
+    out: &mut [u8],
+    collect: bool,
+    mut corrected: F,
+) -> DeQRResult<Corrected>
+where
+    F: FnMut(usize),
+{
+    let ver = version_info(meta);
     let sb_ecc = &ver.ecc[meta.ecc_level as usize];
     let lb_ecc = RSParameters {
         bs: sb_ecc.bs + 1,
@@ -353,63 +1712,174 @@ fn codestream_ecc(meta: &MetaData, ds: RawData) -> DeQRResult<CorrectedDataStrea
     let ecc_offset = sb_ecc.dw * bc + lb_count;
 
     let mut dst_offset = 0;
+    let mut codewords = 0;
     for i in 0..bc {
         let ecc = if i < sb_ecc.ns { sb_ecc } else { &lb_ecc };
-        let dst = &mut out.data[dst_offset..(dst_offset + ecc.bs)];
-        let num_ec = ecc.bs - ecc.dw;
-        #[allow(clippy::needless_range_loop)]
-        for j in 0..ecc.dw {
-            dst[j] = ds.data[j * bc + i];
+        let dst = &mut out[dst_offset..(dst_offset + ecc.bs)];
+
+        // Position of each codeword of the block in the interleaved stream
+        let source = |j: usize| {
+            if j < sb_ecc.dw {
+                j * bc + i
+            } else if j < ecc.dw {
+                /* Long blocks have one more data word, after those of all
+                 * blocks */
+                sb_ecc.dw * bc + i - sb_ecc.ns
+            } else {
+                ecc_offset + (j - ecc.dw) * bc + i
+            }
+        };
+        let is_erasure = |src: usize| erasures[src >> 3] & (1 << (src & 7)) != 0;
+
+        let mut block_erasures = [0; MAX_POLY];
+        let mut num_erasures = 0;
+        for (j, dst) in dst.iter_mut().enumerate() {
+            *dst = raw[source(j)];
+            if is_erasure(source(j)) && num_erasures < MAX_POLY {
+                block_erasures[num_erasures] = j;
+                num_erasures += 1;
+            }
+        }
+        let stats = correct_block(dst, ecc, &block_erasures[..num_erasures])?;
+        if stats.corrected > 0 {
+            for (j, &dst) in dst.iter().enumerate() {
+                if dst != raw[source(j)] {
+                    corrected(source(j));
+                }
+            }
         }
-        for j in 0..num_ec {
-            dst[ecc.dw + j] = ds.data[ecc_offset + j * bc + i];
+        codewords += stats.corrected;
+        if collect {
+            meta.ecc_blocks.push(stats);
         }
-        correct_block(dst, ecc)?;
 
         dst_offset += ecc.dw;
     }
 
-    out.bit_len = dst_offset * 8;
-    Ok(out)
+    Ok(Corrected {
+        bit_len: dst_offset * 8 - half_codeword_bits(meta),
+        codewords,
+    })
+}
+
+/// Look up the size and error correction parameters of a symbol
+fn version_info(meta: &MetaData) -> &'static VersionInfo {
+    match meta.kind {
+        SymbolKind::Qr => &VERSION_DATA_BASE[meta.version.0],
+        SymbolKind::Model1 => &MODEL1_VERSION_DATA_BASE[meta.version.0],
+        SymbolKind::Micro => &MICRO_VERSION_DATA_BASE[meta.version.0],
+        SymbolKind::Rmqr => &RMQR_VERSION_DATA_BASE[meta.version.0],
+    }
 }
 
-fn correct_block(block: &mut [u8], ecc: &RSParameters) -> DeQRResult<()> {
+/// Number of bits missing from the last data word
+///
+/// In M1 and M3 Micro QR codes, the last data word is only 4 bits long.
+fn half_codeword_bits(meta: &MetaData) -> usize {
+    match meta.kind {
+        SymbolKind::Micro if meta.version.0 % 2 == 1 => 4,
+        _ => 0,
+    }
+}
+
+/// Correct a block, given the positions of erased codewords within it
+///
+/// Each erasure only takes up one parity codeword, while an error at an
+/// unknown position takes up two. If the errors are not confined to the
+/// erasures, the block is corrected without them instead.
+fn correct_block(
+    block: &mut [u8],
+    ecc: &RSParameters,
+    erasures: &[usize],
+) -> DeQRResult<BlockStats> {
     assert!(ecc.bs > ecc.dw);
 
     let npar = ecc.bs - ecc.dw;
-    let mut sigma_deriv = [GF256::ZERO; 64];
+    let mut stats = BlockStats {
+        data_codewords: ecc.dw,
+        ecc_codewords: npar,
+        corrected: 0,
+        used: 0,
+    };
 
     // Calculate syndromes. If all 0 there is nothing to do.
     let s = match block_syndromes(&block[..ecc.bs], npar) {
-        Ok(_) => return Ok(()),
+        Ok(_) => return Ok(stats),
         Err(s) => s,
     };
 
-    let sigma = berlekamp_massey(&s, npar);
+    let mut original = [0; 256];
+    let original = &mut original[..ecc.bs];
+    original.copy_from_slice(&block[..ecc.bs]);
+
+    let mut erasures = if erasures.len() < npar { erasures } else { &[] };
+    let errata = match correct_errata(&mut block[..ecc.bs], npar, &s, erasures) {
+        Ok(errata) => errata,
+        Err(_) if !erasures.is_empty() => {
+            block[..ecc.bs].copy_from_slice(original);
+            erasures = &[];
+            correct_errata(&mut block[..ecc.bs], npar, &s, erasures)?
+        }
+        Err(err) => return Err(err),
+    };
+
+    // Every erasure is a root of the errata locator, even if it was correct
+    stats.used = erasures.len() + 2 * (errata - erasures.len());
+    stats.corrected = original
+        .iter()
+        .zip(block.iter())
+        .filter(|(a, b)| a != b)
+        .count();
+    Ok(stats)
+}
+
+/// Correct errors and erasures, given the syndromes of the block
+///
+/// Returns the number of errata located, including all erasures.
+fn correct_errata(
+    block: &mut [u8],
+    npar: usize,
+    s: &[GF256; MAX_POLY],
+    erasures: &[usize],
+) -> DeQRResult<usize> {
+    let bs = block.len();
+    let mut sigma_deriv = [GF256::ZERO; MAX_POLY];
+
+    /* Erasure locator: the product of (1 - X x) for every erased position */
+    let mut gamma = [GF256::ZERO; MAX_POLY];
+    gamma[0] = GF256::ONE;
+    for &j in erasures {
+        let prev = gamma;
+        poly_add(&mut gamma, &prev, GF256::GENERATOR.pow(bs - 1 - j), 1);
+    }
+
+    let sigma = berlekamp_massey(s, npar, &gamma, erasures.len());
     /* Compute derivative of sigma */
-    for i in (1..64).step_by(2) {
+    for i in (1..MAX_POLY).step_by(2) {
         sigma_deriv[i - 1] = sigma[i];
     }
 
     /* Compute error evaluator polynomial */
-    let omega = eloc_poly(&s, &sigma, npar - 1);
+    let omega = eloc_poly(s, &sigma, npar - 1);
 
     /* Find error locations and magnitudes */
-    for i in 0..ecc.bs {
+    let mut errata = 0;
+    for i in 0..bs {
         let xinv = GF256::GENERATOR.pow(255 - i);
         if poly_eval(&sigma, xinv) == GF256::ZERO {
+            errata += 1;
             let sd_x = poly_eval(&sigma_deriv, xinv);
             let omega_x = poly_eval(&omega, xinv);
             if sd_x == GF256::ZERO {
                 return Err(DeQRError::DataEcc);
             }
             let error = omega_x / sd_x;
-            block[ecc.bs - i - 1] = (GF256(block[ecc.bs - i - 1]) + error).0;
+            block[bs - i - 1] = (GF256(block[bs - i - 1]) + error).0;
         }
     }
 
-    match block_syndromes(&block[..ecc.bs], npar) {
-        Ok(_) => Ok(()),
+    match block_syndromes(block, npar) {
+        Ok(_) => Ok(errata),
         Err(_) => Err(DeQRError::DataEcc),
     }
 }
@@ -418,9 +1888,15 @@ fn correct_block(block: &mut [u8], ecc: &RSParameters) -> DeQRResult<()> {
  *
  * Generator polynomial for GF(2^8) is x^8 + x^4 + x^3 + x^2 + 1
  */
-fn block_syndromes(block: &[u8], npar: usize) -> Result<[GF256; 64], [GF256; 64]> {
+
+/// Length of the polynomials used for error correction
+///
+/// Blocks of Model 1 codes have up to 68 parity codewords, one syndrome each.
+const MAX_POLY: usize = 68;
+
+fn block_syndromes(block: &[u8], npar: usize) -> Result<[GF256; MAX_POLY], [GF256; MAX_POLY]> {
     let mut nonzero: bool = false;
-    let mut s = [GF256::ZERO; 64];
+    let mut s = [GF256::ZERO; MAX_POLY];
 
     #[allow(clippy::needless_range_loop)]
     for i in 0..npar {
@@ -439,7 +1915,7 @@ fn block_syndromes(block: &[u8], npar: usize) -> Result<[GF256; 64], [GF256; 64]
     }
 }
 
-fn poly_eval<G>(s: &[G; 64], x: G) -> G
+fn poly_eval<G>(s: &[G; MAX_POLY], x: G) -> G
 where
     G: GaloisField,
 {
@@ -447,15 +1923,15 @@ where
     let mut x_pow = G::ONE;
 
     #[allow(clippy::needless_range_loop)]
-    for i in 0..64 {
+    for i in 0..MAX_POLY {
         sum += s[i] * x_pow;
         x_pow *= x;
     }
     sum
 }
 
-fn eloc_poly(s: &[GF256; 64], sigma: &[GF256; 64], npar: usize) -> [GF256; 64] {
-    let mut omega = [GF256::ZERO; 64];
+fn eloc_poly(s: &[GF256; MAX_POLY], sigma: &[GF256; MAX_POLY], npar: usize) -> [GF256; MAX_POLY] {
+    let mut omega = [GF256::ZERO; MAX_POLY];
     for i in 0..npar {
         let a = sigma[i];
         for j in 0..(npar - i) {
@@ -467,21 +1943,27 @@ fn eloc_poly(s: &[GF256; 64], sigma: &[GF256; 64], npar: usize) -> [GF256; 64] {
 }
 /* ***********************************************************************
  * Berlekamp-Massey algorithm for finding error locator polynomials.
+ *
+ * Starting from the locator of `e` known erasures, the result locates both
+ * the erasures and any additional errors.
  */
-fn berlekamp_massey<G>(s: &[G; 64], n: usize) -> [G; 64]
+fn berlekamp_massey<G>(
+    s: &[G; MAX_POLY],
+    n: usize,
+    erasures: &[G; MAX_POLY],
+    e: usize,
+) -> [G; MAX_POLY]
 where
     G: GaloisField,
 {
-    let mut ts: [G; 64] = [G::ZERO; 64];
-    let mut cs: [G; 64] = [G::ZERO; 64];
-    let mut bs: [G; 64] = [G::ZERO; 64];
-    let mut l: usize = 0;
+    let mut ts: [G; MAX_POLY] = [G::ZERO; MAX_POLY];
+    let mut cs: [G; MAX_POLY] = *erasures;
+    let mut bs: [G; MAX_POLY] = *erasures;
+    let mut l: usize = e;
     let mut m: usize = 1;
     let mut b = G::ONE;
-    bs[0] = G::ONE;
-    cs[0] = G::ONE;
 
-    for n in 0..n {
+    for n in e..n {
         let mut d = s[n];
 
         // Calculate in GF(p):
@@ -494,11 +1976,11 @@ where
 
         if d == G::ZERO {
             m += 1
-        } else if l * 2 <= n {
+        } else if l * 2 <= n + e {
             ts.copy_from_slice(&cs);
             poly_add(&mut cs, &bs, mult, m);
             bs.copy_from_slice(&ts);
-            l = n + 1 - l;
+            l = n + 1 + e - l;
             b = d;
             m = 1
         } else {
@@ -511,7 +1993,7 @@ where
 /* ***********************************************************************
  * Polynomial operations
  */
-fn poly_add<G>(dst: &mut [G; 64], src: &[G; 64], c: G, shift: usize)
+fn poly_add<G>(dst: &mut [G; MAX_POLY], src: &[G; MAX_POLY], c: G, shift: usize)
 where
     G: GaloisField,
 {
@@ -520,9 +2002,9 @@ where
     }
 
     #[allow(clippy::needless_range_loop)]
-    for i in 0..64 {
+    for i in 0..MAX_POLY {
         let p = i + shift;
-        if p >= 64 {
+        if p >= MAX_POLY {
             break;
         }
         let v = src[i];
@@ -531,25 +2013,86 @@ where
 }
 
 /// Reads the code in the "zigzag" pattern, optionally removing the mask
-fn read_data(code: &dyn BitGrid, meta: &MetaData, remove_mask: bool) -> RawData {
-    let mut ds = RawData {
-        data: [0; MAX_PAYLOAD_SIZE],
-        len: 0,
+///
+/// Codewords containing uncertain bits are flagged in `erasures`, one bit
+/// per codeword. A short last data word is padded with zeros to a full byte,
+/// so that the error correction words start on a byte boundary. Remainder
+/// bits beyond the end of `data` are skipped. Returns the number of bits
+/// read.
+fn read_data(
+    code: &dyn BitGrid,
+    meta: &MetaData,
+    remove_mask: bool,
+    data: &mut [u8],
+    erasures: &mut [u8],
+) -> usize {
+    data.fill(0);
+    erasures.fill(0);
+    let mut len = 0;
+    walk_data(code, meta, |bit, y, x| {
+        len = bit + 1;
+        if bit >= data.len() * 8 {
+            return;
+        }
+        if read_bit(code, meta, y, x, remove_mask) {
+            data[bit >> 3] |= 0x80 >> (bit & 7);
+        }
+        if code.uncertain(y, x) {
+            let codeword = bit >> 3;
+            erasures[codeword >> 3] |= 1 << (codeword & 7);
+        }
+    });
+
+    // The top half of the first block of Model 1 codes lies in the corner,
+    // which holds no data
+    if meta.kind == SymbolKind::Model1 {
+        data[0] &= 0x0f;
+    }
+    len
+}
+
+/// Visit all modules holding data, in the order they are read
+///
+/// The visitor is called with the position of the bit in the stream and the
+/// coordinates of the module. Positions skip over the missing half of the
+/// last data word of M1 and M3 Micro QR codes.
+pub(crate) fn walk_data<F>(code: &dyn BitGrid, meta: &MetaData, mut visit: F)
+where
+    F: FnMut(usize, usize, usize),
+{
+    if meta.kind == SymbolKind::Model1 {
+        return walk_model1_data(code, visit);
+    }
+
+    let half_codeword_bits = half_codeword_bits(meta);
+    let data_bits = version_info(meta).ecc[meta.ecc_level as usize].dw * 8 - half_codeword_bits;
+    let mut bit = 0;
+    let mut push = |y: usize, x: usize| {
+        visit(bit, y, x);
+        bit += 1;
+        if bit == data_bits {
+            bit += half_codeword_bits;
+        }
     };
 
-    let mut y = code.size() - 1;
-    let mut x = code.size() - 1;
+    let height = code.height();
+    let mut y = height - 1;
+    // The right edge of rMQR codes is a timing pattern, reading starts next to it
+    let mut x = match meta.kind {
+        SymbolKind::Rmqr => code.width() - 2,
+        _ => code.width() - 1,
+    };
     let mut neg_dir = true;
 
     while x > 0 {
-        if x == 6 {
+        if x == 6 && meta.kind == SymbolKind::Qr {
             x -= 1;
         }
-        if !reserved_cell(meta.version, y, x) {
-            ds.push(read_bit(code, meta, y, x, remove_mask));
+        if !reserved(meta, y, x) {
+            push(y, x);
         }
-        if !reserved_cell(meta.version, y, x - 1) {
-            ds.push(read_bit(code, meta, y, x - 1, remove_mask));
+        if !reserved(meta, y, x - 1) {
+            push(y, x - 1);
         }
 
         let (new_y, new_neg_dir) = match (y, neg_dir) {
@@ -557,9 +2100,9 @@ fn read_data(code: &dyn BitGrid, meta: &MetaData, remove_mask: bool) -> RawData
                 x = x.saturating_sub(2);
                 (0, false)
             }
-            (y, false) if y == code.size() - 1 => {
+            (y, false) if y == height - 1 => {
                 x = x.saturating_sub(2);
-                (code.size() - 1, true)
+                (height - 1, true)
             }
             (y, true) => (y - 1, true),
             (y, false) => (y + 1, false),
@@ -568,33 +2111,155 @@ fn read_data(code: &dyn BitGrid, meta: &MetaData, remove_mask: bool) -> RawData
         y = new_y;
         neg_dir = new_neg_dir;
     }
+}
+
+/// Visit the data modules of a Model 1 code
+///
+/// Instead of following a single path, each codeword of a Model 1 code fills
+/// a fixed block of modules. The blocks are arranged in columns, starting at
+/// the bottom right. The two columns along the right edge and the columns
+/// between the left finder patterns hold blocks 2 modules wide and 4 high,
+/// all other columns blocks 4 modules wide and 2 high. Blocks taken up by
+/// extension patterns along the right and bottom edges are skipped.
+fn walk_model1_data<F>(code: &dyn BitGrid, mut visit: F)
+where
+    F: FnMut(usize, usize, usize),
+{
+    let size = code.size();
+    let columns = size / 4 + 3;
+    let mut bit = 0;
+    let mut read_block = |x: usize, y: usize, width: usize| {
+        for b in 0..8 {
+            visit(bit, y - b / width, x - b % width);
+            bit += 1;
+        }
+    };
+
+    for j in 0..columns {
+        if j < 2 {
+            let rows = (size - 8) / 4;
+            for i in 0..rows {
+                // Skip the extension patterns on the right edge
+                if j == 0 && i % 2 == 0 && 0 < i && i < rows - 1 {
+                    continue;
+                }
+                read_block(size - 1 - j * 2, size - 1 - i * 4, 2);
+            }
+        } else if columns - j <= 4 {
+            // Skip the vertical timing pattern
+            let x = (columns - j) * 2 - 1 + (columns - j == 4) as usize;
+            for i in 0..(size - 16) / 4 {
+                read_block(x, size - 9 - i * 4, 2);
+            }
+        } else {
+            let rows = size / 2;
+            for i in 0..rows {
+                // Skip the top right finder pattern
+                if j == 2 && i >= rows - 4 {
+                    continue;
+                }
+                // Skip the extension patterns on the bottom edge
+                if i == 0 && j % 2 == 1 && j + 1 != columns - 4 {
+                    continue;
+                }
+                // Skip the horizontal timing pattern
+                let y = size - 1 - i * 2 - (i >= rows - 3) as usize;
+                read_block(size - 5 - (j - 2) * 4, y, 4);
+            }
+        }
+    }
+}
+
+// The read_bit() function can optionally consider the mask.
+// This allows bits to be read as they appear "physically" in the QR code or with the mask removed, reflecting the actual code.
+fn read_bit(code: &dyn BitGrid, meta: &MetaData, y: usize, x: usize, remove_mask: bool) -> bool {
+    /// The Micro QR masks are a subset of the regular ones
+    const MICRO_MASKS: [u16; 4] = [1, 4, 6, 7];
+
+    let mask = match meta.kind {
+        SymbolKind::Qr | SymbolKind::Model1 => meta.mask,
+        SymbolKind::Micro => MICRO_MASKS[meta.mask as usize],
+        SymbolKind::Rmqr => meta.mask,
+    };
+    let mut v = code.bit(y, x) as u8;
+    if remove_mask && mask_bit(mask, y, x) {
+        v ^= 1
+    }
+
+    v != 0
+}
+
+pub(crate) fn mask_bit(mask: u16, y: usize, x: usize) -> bool {
+    match mask {
+        0 => 0 == (y + x) % 2,
+        1 => 0 == y % 2,
+        2 => 0 == x % 3,
+        3 => 0 == (y + x) % 3,
+        4 => 0 == ((y / 2) + (x / 3)) % 2,
+        5 => 0 == ((y * x) % 2 + (y * x) % 3),
+        6 => 0 == ((y * x) % 2 + (y * x) % 3) % 2,
+        7 => 0 == ((y * x) % 3 + (y + x) % 2) % 2,
+        _ => panic!("Unknown mask value"),
+    }
+}
+
+fn reserved(meta: &MetaData, i: usize, j: usize) -> bool {
+    match meta.kind {
+        SymbolKind::Qr => reserved_cell(meta.version, i, j),
+        SymbolKind::Model1 => unreachable!("Model 1 codes are read block by block"),
+        SymbolKind::Micro => reserved_micro_cell(i, j),
+        SymbolKind::Rmqr => reserved_rmqr_cell(meta.version, i, j),
+    }
+}
+
+fn reserved_micro_cell(i: usize, j: usize) -> bool {
+    /* Finder + format: top left, timing patterns along the edges */
+    (i < 9 && j < 9) || i == 0 || j == 0
+}
+
+fn reserved_rmqr_cell(version: Version, i: usize, j: usize) -> bool {
+    let ver = &RMQR_VERSION_DATA_BASE[version.0];
+    let (width, height) = version.to_rmqr_size();
+
+    /* Timing patterns and corner finders along the edges */
+    if i == 0 || j == 0 || i == height - 1 || j == width - 1 {
+        return true;
+    }
+
+    /* Finder + separator: top left. In R7 codes, the separator would end up
+     * on the bottom edge.
+     */
+    if i < 8 && j < 8 {
+        return true;
+    }
 
-    ds
-}
+    /* Format: next to the finder */
+    if (i < 6 && j < 11) || (i < 4 && j == 11) {
+        return true;
+    }
 
-// The read_bit() function can optionally consider the mask.
-// This allows bits to be read as they appear "physically" in the QR code or with the mask removed, reflecting the actual code.
-fn read_bit(code: &dyn BitGrid, meta: &MetaData, y: usize, x: usize, remove_mask: bool) -> bool {
-    let mut v = code.bit(y, x) as u8;
-    if remove_mask && mask_bit(meta.mask, y, x) {
-        v ^= 1
+    /* Sub-finder: bottom right */
+    if i + 5 >= height && j + 5 >= width {
+        return true;
     }
 
-    v != 0
-}
+    /* Format: next to the sub-finder */
+    if (i + 6 >= height && (width - 8..width - 5).contains(&j))
+        || (i + 6 == height && (width - 5..width - 2).contains(&j))
+    {
+        return true;
+    }
 
-fn mask_bit(mask: u16, y: usize, x: usize) -> bool {
-    match mask {
-        0 => 0 == (y + x) % 2,
-        1 => 0 == y % 2,
-        2 => 0 == x % 3,
-        3 => 0 == (y + x) % 3,
-        4 => 0 == ((y / 2) + (x / 3)) % 2,
-        5 => 0 == ((y * x) % 2 + (y * x) % 3),
-        6 => 0 == ((y * x) % 2 + (y * x) % 3) % 2,
-        7 => 0 == ((y * x) % 3 + (y + x) % 2) % 2,
-        _ => panic!("Unknown mask value"),
+    /* Corner finders: top right and, in codes taller than R9, bottom left */
+    if (i == 1 && j == width - 2) || (height > 9 && i == height - 2 && j == 1) {
+        return true;
     }
+
+    /* Vertical timing patterns, with an alignment pattern at either end */
+    ver.apat
+        .iter()
+        .take_while(|&&x| x != 0)
+        .any(|&x| j == x || (x.abs_diff(j) < 2 && (i < 3 || i + 3 >= height)))
 }
 
 fn reserved_cell(version: Version, i: usize, j: usize) -> bool {
@@ -662,7 +2327,9 @@ fn correct_format(mut word: u16) -> DeQRResult<u16> {
      * to get S_1 .. S_6 (but we index them from 0).
      */
     if let Err(s) = format_syndromes(word) {
-        let sigma = berlekamp_massey(&s, 6);
+        let mut one = [GF16::ZERO; MAX_POLY];
+        one[0] = GF16::ONE;
+        let sigma = berlekamp_massey(&s, 6, &one, 0);
 
         /* Now, find the roots of the polynomial */
         for i in 0..15 {
@@ -678,47 +2345,331 @@ fn correct_format(mut word: u16) -> DeQRResult<u16> {
 }
 
 fn read_format(code: &dyn BitGrid) -> DeQRResult<MetaData> {
-    let mut format = 0;
+    if code.width() != code.height() {
+        return read_rmqr_format(code);
+    }
+    if code.size() < 21 {
+        return read_micro_format(code);
+    }
+    read_qr_format(code, SymbolKind::Qr)
+}
+
+/// Check whether a square grid belongs to a mirrored code
+///
+/// The format information of a mirrored code is only close to a valid code
+/// word when the grid is transposed. If both readings are equally close, the
+/// grid is assumed to be in its true orientation.
+pub(crate) fn is_mirrored(code: &dyn BitGrid) -> bool {
+    if code.width() != code.height() {
+        return false;
+    }
+    format_distance(&crate::MirroredGrid(code)) < format_distance(code)
+}
+
+/// Number of bit errors in the best copy of the format information of a
+/// square grid
+///
+/// Grids of regular size are also read as Model 1 codes.
+fn format_distance(code: &dyn BitGrid) -> u32 {
+    let distance = |word: u16| {
+        (0..32)
+            .map(|data| (bch_15_5(data) ^ word).count_ones())
+            .min()
+            .expect("there are 32 code words")
+    };
+    if code.size() < 21 {
+        return distance(read_micro_format_word(code));
+    }
+    [SymbolKind::Qr, SymbolKind::Model1]
+        .into_iter()
+        .flat_map(|kind| read_format_copies(code, kind))
+        .map(distance)
+        .min()
+        .expect("there are 4 copies")
+}
+
+/// Read the format of a Model 1 code
+///
+/// Model 1 codes place their format information just like regular QR codes,
+/// only with a different mask.
+fn read_model1_format(code: &dyn BitGrid) -> DeQRResult<MetaData> {
+    let version = Version::from_size(code.size())?;
+    if code.width() != code.height() || version.0 >= MODEL1_VERSION_DATA_BASE.len() {
+        return Err(DeQRError::InvalidVersion);
+    }
+    read_qr_format(code, SymbolKind::Model1)
+}
+
+/// Read the format information of a regular or Model 1 QR code
+fn read_qr_format(code: &dyn BitGrid, kind: SymbolKind) -> DeQRResult<MetaData> {
+    // Check format, try other location if needed
+    let copies = read_format_copies(code, kind);
+    let verified_format = correct_format(copies[0]).or_else(|_| correct_format(copies[1]))?;
+    qr_metadata(code, kind, verified_format >> 10)
+}
+
+/// Read both copies of the format information of a regular or Model 1 QR
+/// code, with the mask removed
+///
+/// The first copy surrounds the top left finder, the second one is split
+/// between the other two finders.
+fn read_format_copies(code: &dyn BitGrid, kind: SymbolKind) -> [u16; 2] {
+    let format_mask = format_mask(kind);
 
-    // Try first location
     const XS: [usize; 15] = [8, 8, 8, 8, 8, 8, 8, 8, 7, 5, 4, 3, 2, 1, 0];
     const YS: [usize; 15] = [0, 1, 2, 3, 4, 5, 7, 8, 8, 8, 8, 8, 8, 8, 8];
+    let mut first = 0;
     for i in (0..15).rev() {
-        format = (format << 1) | code.bit(YS[i], XS[i]) as u16;
+        first = (first << 1) | code.bit(YS[i], XS[i]) as u16;
     }
-    format ^= 0x5412;
 
-    // Check format, try other location if needed
-    let verified_format = correct_format(format).or_else(|_| {
-        let mut format = 0;
-        for i in 0..7 {
-            format = (format << 1) | code.bit(code.size() - 1 - i, 8) as u16;
-        }
-        for i in 0..8 {
-            format = (format << 1) | code.bit(8, code.size() - 8 + i) as u16;
-        }
-        format ^= 0x5412;
-        correct_format(format)
-    })?;
+    let mut second = 0;
+    for i in 0..7 {
+        second = (second << 1) | code.bit(code.size() - 1 - i, 8) as u16;
+    }
+    for i in 0..8 {
+        second = (second << 1) | code.bit(8, code.size() - 8 + i) as u16;
+    }
+
+    [first ^ format_mask, second ^ format_mask]
+}
 
-    let fdata = verified_format >> 10;
+/// Mask applied to the format information of a regular or Model 1 QR code
+fn format_mask(kind: SymbolKind) -> u16 {
+    match kind {
+        SymbolKind::Model1 => 0x2825,
+        _ => 0x5412,
+    }
+}
+
+/// Create the metadata of a regular or Model 1 QR code from the 5 data bits
+/// of its format information
+fn qr_metadata(code: &dyn BitGrid, kind: SymbolKind, fdata: u16) -> DeQRResult<MetaData> {
     let ecc_level = fdata >> 3;
     let mask = fdata & 7;
     let version = Version::from_size(code.size())?;
+    if kind == SymbolKind::Qr && version.0 >= 7 {
+        check_version_info(code, version)?;
+    }
 
     Ok(MetaData {
+        kind,
         version,
         ecc_level,
         mask,
+        eci: Vec::new(),
+        structured_append: None,
+        fnc1: None,
+        ecc_blocks: Vec::new(),
+        mirrored: false,
+    })
+}
+
+fn read_micro_format(code: &dyn BitGrid) -> DeQRResult<MetaData> {
+    /// Version and error correction level for each symbol number
+    const SYMBOLS: [(usize, u16); 8] = [
+        (1, 1),
+        (2, 1),
+        (2, 0),
+        (3, 1),
+        (3, 0),
+        (4, 1),
+        (4, 0),
+        (4, 3),
+    ];
+
+    let version = Version::from_micro_size(code.size())?;
+    let fdata = correct_format(read_micro_format_word(code))? >> 10;
+    let (symbol_version, ecc_level) = SYMBOLS[(fdata >> 2) as usize];
+    if symbol_version != version.0 {
+        return Err(DeQRError::InvalidVersion);
+    }
+
+    Ok(MetaData {
+        kind: SymbolKind::Micro,
+        version,
+        ecc_level,
+        mask: fdata & 3,
+        eci: Vec::new(),
+        structured_append: None,
+        fnc1: None,
+        ecc_blocks: Vec::new(),
+        mirrored: false,
+    })
+}
+
+/// Read the format information of a Micro QR code, with the mask removed
+///
+/// There is only one copy, next to the finder.
+fn read_micro_format_word(code: &dyn BitGrid) -> u16 {
+    let mut format = 0;
+    for x in 1..9 {
+        format = (format << 1) | code.bit(8, x) as u16;
+    }
+    for y in (1..8).rev() {
+        format = (format << 1) | code.bit(y, 8) as u16;
+    }
+    format ^ 0x4445
+}
+
+/// Mask of the rMQR format information next to the finder
+pub(crate) const RMQR_FINDER_FORMAT_MASK: u32 = 0x1fab2;
+/// Mask of the rMQR format information next to the sub-finder
+const RMQR_SUB_FINDER_FORMAT_MASK: u32 = 0x20a7b;
+
+fn read_rmqr_format(code: &dyn BitGrid) -> DeQRResult<MetaData> {
+    let width = code.width();
+    let height = code.height();
+    let size_version = Version::from_rmqr_size(width, height)?;
+
+    // Try first location, next to the finder
+    let mut format = 0;
+    for y in (1..4).rev() {
+        format = (format << 1) | code.bit(y, 11) as u32;
+    }
+    for x in (8..11).rev() {
+        for y in (1..6).rev() {
+            format = (format << 1) | code.bit(y, x) as u32;
+        }
+    }
+
+    // Check format, try other location next to the sub-finder if needed
+    let (version, ecc_level) =
+        decode_rmqr_format(format, RMQR_FINDER_FORMAT_MASK).or_else(|_| {
+            let mut format = 0;
+            for x in 3..6 {
+                format = (format << 1) | code.bit(height - 6, width - x) as u32;
+            }
+            for x in 6..9 {
+                for y in 2..7 {
+                    format = (format << 1) | code.bit(height - y, width - x) as u32;
+                }
+            }
+            decode_rmqr_format(format, RMQR_SUB_FINDER_FORMAT_MASK)
+        })?;
+
+    if version != size_version {
+        return Err(DeQRError::InvalidVersion);
+    }
+
+    Ok(MetaData {
+        kind: SymbolKind::Rmqr,
+        version,
+        ecc_level,
+        mask: 4,
+        eci: Vec::new(),
+        structured_append: None,
+        fnc1: None,
+        ecc_blocks: Vec::new(),
+        mirrored: false,
     })
 }
+
+/// Correct rMQR format information, returning version and error correction
+/// level
+///
+/// The format is protected by a (18, 6) BCH code, which corrects up to 3
+/// errors. With only 64 code words, we simply pick the closest one.
+pub(crate) fn decode_rmqr_format(format: u32, mask: u32) -> DeQRResult<(Version, u16)> {
+    let (distance, fdata) = (0..64)
+        .map(|fdata| ((bch_18_6(fdata) ^ mask ^ format).count_ones(), fdata))
+        .min()
+        .expect("there are 64 code words");
+    if distance > 3 {
+        return Err(DeQRError::FormatEcc);
+    }
+
+    // Error correction level H is stored as 1, M as 0
+    let ecc_level = ((fdata >> 5) << 1) as u16;
+    Ok((Version((fdata & 0x1f) as usize + 1), ecc_level))
+}
+
+/// Assemble one copy of the version information of a QR code
+///
+/// Each copy is a block of 6 by 3 modules. `module(i, j)` returns the module
+/// `i` along the finder pattern and `j` across, i.e. row `i` and column
+/// `size - 11 + j` for the copy next to the top right finder. The copy next
+/// to the bottom left finder is transposed.
+pub(crate) fn read_version_info<F>(mut module: F) -> u32
+where
+    F: FnMut(usize, usize) -> bool,
+{
+    let mut info = 0;
+    for i in (0..6).rev() {
+        for j in (0..3).rev() {
+            info = (info << 1) | module(i, j) as u32;
+        }
+    }
+    info
+}
+
+/// Correct QR version information, returning the version
+///
+/// Version information uses the same (18, 6) BCH code as rMQR format
+/// information, but is not masked. Only versions 7 to 40 carry it.
+pub(crate) fn decode_version_info(info: u32) -> DeQRResult<Version> {
+    let (distance, version) = (7..VERSION_DATA_BASE.len() as u32)
+        .map(|version| ((bch_18_6(version) ^ info).count_ones(), version))
+        .min()
+        .expect("there are 34 code words");
+    if distance > 3 {
+        return Err(DeQRError::InvalidVersion);
+    }
+    Ok(Version(version as usize))
+}
+
+/// Check the version information of a regular QR code against its size
+///
+/// Fails only if both copies of the version information agree on a version
+/// that does not match the size. A single copy may be damaged so badly that
+/// it decodes to the wrong version.
+fn check_version_info(code: &dyn BitGrid, version: Version) -> DeQRResult<()> {
+    let size = code.size();
+    let top_right = read_version_info(|i, j| code.bit(i, size - 11 + j));
+    let bottom_left = read_version_info(|i, j| code.bit(size - 11 + j, i));
+    match (
+        decode_version_info(top_right),
+        decode_version_info(bottom_left),
+    ) {
+        (Ok(a), Ok(b)) if a == b && a != version => Err(DeQRError::InvalidVersion),
+        _ => Ok(()),
+    }
+}
+
+/// Append the 10 check bits of the (15, 5) BCH code to 5 data bits
+pub(crate) fn bch_15_5(data: u16) -> u16 {
+    /* Generator polynomial is x^10 + x^8 + x^5 + x^4 + x^2 + x + 1 */
+    const GENERATOR: u16 = 0x537;
+
+    let mut rem = data << 10;
+    for i in (0..5).rev() {
+        if rem & (1 << (i + 10)) != 0 {
+            rem ^= GENERATOR << i;
+        }
+    }
+    (data << 10) | rem
+}
+
+/// Append the 12 check bits of the (18, 6) BCH code to 6 data bits
+pub(crate) fn bch_18_6(data: u32) -> u32 {
+    /* Generator polynomial is x^12 + x^11 + x^10 + x^9 + x^8 + x^5 + x^2 + 1 */
+    const GENERATOR: u32 = 0x1f25;
+
+    let mut rem = data << 12;
+    for i in (0..6).rev() {
+        if rem & (1 << (i + 12)) != 0 {
+            rem ^= GENERATOR << i;
+        }
+    }
+    (data << 12) | rem
+}
 /* ***********************************************************************
  * Format value error correction
  *
  * Generator polynomial for GF(2^4) is x^4 + x + 1
  */
-fn format_syndromes(u: u16) -> Result<[GF16; 64], [GF16; 64]> {
-    let mut result = [GF16(0); 64];
+fn format_syndromes(u: u16) -> Result<[GF16; MAX_POLY], [GF16; MAX_POLY]> {
+    let mut result = [GF16(0); MAX_POLY];
     let mut nonzero = false;
 
     #[allow(clippy::needless_range_loop)]
@@ -743,6 +2694,926 @@ fn format_syndromes(u: u16) -> Result<[GF16; 64], [GF16; 64]> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::charset::TextDecoder;
+    use alloc::vec;
+
+    /// Bytes of a data stream built from (value, bit count) pairs
+    struct Fields {
+        data: Vec<u8>,
+        bit_len: usize,
+    }
+
+    impl Fields {
+        fn new(fields: &[(usize, usize)]) -> Self {
+            let mut data = Vec::new();
+            let mut bit_len = 0;
+            for &(value, nbits) in fields {
+                for i in (0..nbits).rev() {
+                    if bit_len % 8 == 0 {
+                        data.push(0);
+                    }
+                    if (value >> i) & 1 != 0 {
+                        data[bit_len >> 3] |= 0x80 >> (bit_len & 7);
+                    }
+                    bit_len += 1;
+                }
+            }
+            Fields { data, bit_len }
+        }
+
+        fn stream(&self) -> CorrectedDataStream<'_> {
+            CorrectedDataStream {
+                data: &self.data,
+                ptr: 0,
+                bit_len: self.bit_len,
+            }
+        }
+    }
+
+    fn meta_v1() -> MetaData {
+        MetaData {
+            kind: SymbolKind::Qr,
+            version: Version(1),
+            ecc_level: 0,
+            mask: 0,
+            eci: Vec::new(),
+            structured_append: None,
+            fnc1: None,
+            ecc_blocks: Vec::new(),
+            mirrored: false,
+        }
+    }
+
+    #[test]
+    fn test_eci_assignment_numbers() {
+        let fields = Fields::new(&[(26, 8)]);
+        let mut ds = fields.stream();
+        assert_eq!(decode_eci(&mut ds), Ok(26));
+        let fields = Fields::new(&[(0x83, 8), (0x83, 8)]);
+        let mut ds = fields.stream();
+        assert_eq!(decode_eci(&mut ds), Ok(899));
+        let fields = Fields::new(&[(0xc1, 8), (0x86a0, 16)]);
+        let mut ds = fields.stream();
+        assert_eq!(decode_eci(&mut ds), Ok(100_000));
+        let fields = Fields::new(&[(0x83, 8)]);
+        let mut ds = fields.stream();
+        assert_eq!(decode_eci(&mut ds), Err(DeQRError::DataUnderflow));
+    }
+
+    #[test]
+    fn test_eci_transcodes_segments() {
+        let fields = Fields::new(&[
+            // ECI 3 (ISO-8859-1), byte mode "caf\xe9"
+            (7, 4),
+            (3, 8),
+            (4, 4),
+            (4, 8),
+            (b'c' as usize, 8),
+            (b'a' as usize, 8),
+            (b'f' as usize, 8),
+            (0xe9, 8),
+            // ECI 20 (Shift JIS), byte mode "\x93\xfa"
+            (7, 4),
+            (20, 8),
+            (4, 4),
+            (2, 8),
+            (0x93, 8),
+            (0xfa, 8),
+            (0, 4),
+        ]);
+        let ds = fields.stream();
+
+        let mut meta = meta_v1();
+        let mut text = TextDecoder::new();
+        decode_payload(&mut meta, ds.clone(), &mut text, true).unwrap();
+        assert_eq!(text.finish().unwrap().text, "café日");
+        assert_eq!(meta.eci, vec![3, 20]);
+
+        let mut meta = meta_v1();
+        let mut raw = Vec::new();
+        decode_payload(&mut meta, ds, &mut WriterSink(&mut raw), true).unwrap();
+        assert_eq!(raw, b"caf\xe9\x93\xfa");
+        assert_eq!(meta.eci, vec![3, 20]);
+    }
+
+    #[test]
+    fn test_kanji_to_text() {
+        let fields = Fields::new(&[
+            // Kanji mode "日本"
+            (8, 4),
+            (2, 8),
+            (3642, 13),
+            (4091, 13),
+            // ECI 25 (UTF-16BE) does not apply to alphanumeric mode
+            (7, 4),
+            (25, 8),
+            (2, 4),
+            (2, 9),
+            (45 * 10 + 11, 11),
+            (0, 4),
+        ]);
+        let ds = fields.stream();
+
+        let mut meta = meta_v1();
+        let mut text = TextDecoder::new();
+        decode_payload(&mut meta, ds.clone(), &mut text, true).unwrap();
+        assert_eq!(text.finish().unwrap().text, "日本AB");
+
+        let mut meta = meta_v1();
+        let mut raw = Vec::new();
+        decode_payload(&mut meta, ds, &mut WriterSink(&mut raw), true).unwrap();
+        assert_eq!(raw, b"\x93\xfa\x96\x7bAB");
+    }
+
+    #[test]
+    fn test_segments() {
+        let fields = Fields::new(&[
+            // Numeric "123"
+            (1, 4),
+            (3, 10),
+            (123, 10),
+            // ECI 26 (UTF-8)
+            (7, 4),
+            (26, 8),
+            // Byte "é"
+            (4, 4),
+            (2, 8),
+            (0xc3, 8),
+            (0xa9, 8),
+            // Alphanumeric "A"
+            (2, 4),
+            (1, 9),
+            (10, 6),
+            (0, 4),
+        ]);
+        let ds = fields.stream();
+
+        let mut meta = meta_v1();
+        let mut collector = SegmentCollector::new();
+        decode_payload(&mut meta, ds, &mut collector, true).unwrap();
+        assert_eq!(
+            collector.segments,
+            vec![
+                Segment {
+                    mode: Mode::Numeric,
+                    count: 3,
+                    bit_offset: 0,
+                    eci: None,
+                    data: b"123".to_vec(),
+                },
+                Segment {
+                    mode: Mode::Eci,
+                    count: 0,
+                    bit_offset: 24,
+                    eci: Some(26),
+                    data: Vec::new(),
+                },
+                Segment {
+                    mode: Mode::Byte,
+                    count: 2,
+                    bit_offset: 36,
+                    eci: Some(26),
+                    data: "é".as_bytes().to_vec(),
+                },
+                Segment {
+                    mode: Mode::Alphanumeric,
+                    count: 1,
+                    bit_offset: 64,
+                    eci: Some(26),
+                    data: b"A".to_vec(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_structured_append_header() {
+        let fields = Fields::new(&[
+            // Symbol 2 of 3, parity 0x5a
+            (3, 4),
+            (1, 4),
+            (2, 4),
+            (0x5a, 8),
+            (4, 4),
+            (1, 8),
+            (b'x' as usize, 8),
+            (0, 4),
+        ]);
+        let ds = fields.stream();
+
+        let mut meta = meta_v1();
+        let mut collector = SegmentCollector::new();
+        decode_payload(&mut meta, ds, &mut collector, true).unwrap();
+        assert_eq!(
+            meta.structured_append,
+            Some(StructuredAppend {
+                index: 1,
+                total: 3,
+                parity: 0x5a,
+            })
+        );
+        assert_eq!(collector.segments.len(), 2);
+        assert_eq!(collector.segments[0].mode, Mode::StructuredAppend);
+        assert_eq!(collector.segments[1].bit_offset, 20);
+        assert_eq!(collector.segments[1].data, b"x");
+    }
+
+    #[test]
+    fn test_default_charset_latin1() {
+        let fields = Fields::new(&[
+            (4, 4),
+            (6, 8),
+            (b'S' as usize, 8),
+            (b't' as usize, 8),
+            (b'r' as usize, 8),
+            (b'a' as usize, 8),
+            (0xdf, 8),
+            (b'e' as usize, 8),
+            (0, 4),
+        ]);
+        let ds = fields.stream();
+
+        let mut meta = meta_v1();
+        let mut text = TextDecoder::new();
+        decode_payload(&mut meta, ds, &mut text, true).unwrap();
+        let decoded = text.finish().unwrap();
+        assert_eq!(decoded.text, "Straße");
+        assert_eq!(decoded.charset, Some(crate::CharacterSet::Iso8859_1));
+    }
+
+    #[test]
+    fn test_fnc1_first() {
+        let fields = Fields::new(&[
+            // FNC1 in first position, alphanumeric "A%%B%C%"
+            (5, 4),
+            (2, 4),
+            (7, 9),
+            (10 * 45 + 38, 11),
+            (38 * 45 + 11, 11),
+            (38 * 45 + 12, 11),
+            (38, 6),
+            (0, 4),
+        ]);
+        let ds = fields.stream();
+
+        let mut meta = meta_v1();
+        let mut raw = Vec::new();
+        decode_payload(&mut meta, ds, &mut WriterSink(&mut raw), true).unwrap();
+        assert_eq!(raw, b"A%B\x1dC\x1d");
+        assert_eq!(meta.fnc1, Some(Fnc1::First));
+        assert_eq!(meta.symbology_identifier(), "]Q3");
+    }
+
+    #[test]
+    fn test_fnc1_second() {
+        let fields = Fields::new(&[
+            // FNC1 in second position, application indicator "A"
+            (9, 4),
+            (165, 8),
+            (2, 4),
+            (1, 9),
+            (38, 6),
+            (0, 4),
+        ]);
+        let ds = fields.stream();
+
+        let mut meta = meta_v1();
+        let mut collector = SegmentCollector::new();
+        decode_payload(&mut meta, ds, &mut collector, true).unwrap();
+        assert_eq!(meta.fnc1, Some(Fnc1::Second(165)));
+        assert_eq!(meta.fnc1.unwrap().application_indicator().unwrap(), "A");
+        assert_eq!(meta.symbology_identifier(), "]Q5");
+        assert_eq!(collector.segments[0].mode, Mode::Fnc1Second);
+        assert_eq!(collector.segments[1].data, b"\x1d");
+
+        assert_eq!(Fnc1::Second(7).application_indicator().unwrap(), "07");
+        let fields = Fields::new(&[(100, 8)]);
+        let mut ds = fields.stream();
+        assert_eq!(
+            decode_application_indicator(&mut ds),
+            Err(DeQRError::UnknownDataType)
+        );
+    }
+
+    #[test]
+    fn test_micro_modes() {
+        let fields = Fields::new(&[
+            // M3: numeric "7", alphanumeric "A", terminator cut short
+            (0, 2),
+            (1, 5),
+            (7, 4),
+            (1, 2),
+            (1, 4),
+            (10, 6),
+            (0, 5),
+        ]);
+        let ds = fields.stream();
+
+        let mut meta = meta_v1();
+        meta.kind = SymbolKind::Micro;
+        meta.version = Version(3);
+        let mut collector = SegmentCollector::new();
+        decode_payload(&mut meta, ds, &mut collector, true).unwrap();
+        assert_eq!(collector.segments.len(), 2);
+        assert_eq!(collector.segments[0].mode, Mode::Numeric);
+        assert_eq!(collector.segments[0].data, b"7");
+        assert_eq!(collector.segments[1].mode, Mode::Alphanumeric);
+        assert_eq!(collector.segments[1].bit_offset, 11);
+        assert_eq!(collector.segments[1].data, b"A");
+
+        // M1 only knows numeric mode, without any mode indicator
+        let fields = Fields::new(&[(2, 3), (42, 7), (0, 3)]);
+        let ds = fields.stream();
+        let mut meta = meta_v1();
+        meta.kind = SymbolKind::Micro;
+        meta.version = Version(1);
+        let mut raw = Vec::new();
+        decode_payload(&mut meta, ds, &mut WriterSink(&mut raw), true).unwrap();
+        assert_eq!(raw, b"42");
+    }
+
+    #[test]
+    fn test_rmqr_modes() {
+        let fields = Fields::new(&[
+            // R7x43: numeric "123", ECI 26, byte "A", terminator
+            (1, 3),
+            (3, 4),
+            (123, 10),
+            (7, 3),
+            (26, 8),
+            (3, 3),
+            (1, 3),
+            (0x41, 8),
+            (0, 3),
+        ]);
+        let ds = fields.stream();
+
+        let mut meta = meta_v1();
+        meta.kind = SymbolKind::Rmqr;
+        let mut collector = SegmentCollector::new();
+        decode_payload(&mut meta, ds, &mut collector, true).unwrap();
+        assert_eq!(meta.eci, vec![26]);
+        let modes: Vec<_> = collector.segments.iter().map(|s| s.mode).collect();
+        assert_eq!(modes, vec![Mode::Numeric, Mode::Eci, Mode::Byte]);
+        assert_eq!(collector.segments[0].data, b"123");
+        assert_eq!(collector.segments[2].data, b"A");
+    }
+
+    #[test]
+    fn test_version_info() {
+        assert_eq!(decode_version_info(0x07c94), Ok(Version(7)));
+        assert_eq!(decode_version_info(0x28c69), Ok(Version(40)));
+        assert_eq!(decode_version_info(0x28c69 ^ 0x20101), Ok(Version(40)));
+        assert_eq!(decode_version_info(0), Err(DeQRError::InvalidVersion));
+
+        // Bits are read along the finder first, starting at the far corner
+        let info = read_version_info(|i, j| 0x07c94 & (1 << (i * 3 + j)) != 0);
+        assert_eq!(info, 0x07c94);
+    }
+
+    #[test]
+    fn test_rmqr_format() {
+        assert_eq!(
+            decode_rmqr_format(0x1fab2, RMQR_FINDER_FORMAT_MASK),
+            Ok((Version(1), 0))
+        );
+
+        // R17x139, level H, with 3 errors
+        let format = bch_18_6(0x3f) ^ RMQR_SUB_FINDER_FORMAT_MASK;
+        assert_eq!(
+            decode_rmqr_format(format ^ 0x10204, RMQR_SUB_FINDER_FORMAT_MASK),
+            Ok((Version(32), 2))
+        );
+        assert_eq!(
+            decode_rmqr_format(format ^ 0x10284, RMQR_SUB_FINDER_FORMAT_MASK),
+            Err(DeQRError::FormatEcc)
+        );
+    }
+
+    /// An rMQR code of 27x11 modules holding "ABCDEF"
+    const RMQR_ROWS: [&str; 11] = [
+        "XXXXXXX X X X X X X X X XXX",
+        "X     X  XX        X  X X X",
+        "X XXX X    X  XX X   X   XX",
+        "X XXX X XXXX XX X  XXXXXX  ",
+        "X XXX X  X X XX  XX   XXX X",
+        "X     X XXX  X XX  XXXX  X ",
+        "XXXXXXX     X   XX  X XXXXX",
+        "           X   X   X  X   X",
+        "XXXX  X   X X XX XXXXXX X X",
+        "X XX XXXXXX XXX  XXXX X   X",
+        "XXX X X X X X X X X X XXXXX",
+    ];
+
+    fn rmqr_module(x: usize, y: usize) -> bool {
+        RMQR_ROWS[y].as_bytes()[x] == b'X'
+    }
+
+    #[test]
+    fn test_rmqr_grid() {
+        let grid = crate::SimpleGrid::from_func_rect(27, 11, rmqr_module);
+
+        let mut raw = Vec::new();
+        let meta = decode(&grid, &mut raw).unwrap();
+        assert_eq!(meta.kind, SymbolKind::Rmqr);
+        assert_eq!(meta.version.to_rmqr_size(), (27, 11));
+        assert_eq!(meta.ecc_level, 2);
+        assert_eq!(raw, b"ABCDEF");
+    }
+
+    /// A Model 1 code of version 1 holding "Model 1"
+    const MODEL1_ROWS: [&str; 21] = [
+        "XXXXXXX XXXX  XXXXXXX",
+        "X     X  XX   X     X",
+        "X XXX X X  XX X XXX X",
+        "X XXX X XX XX X XXX X",
+        "X XXX X XX X  X XXX X",
+        "X     X   X X X     X",
+        "XXXXXXX X X X XXXXXXX",
+        "         X X         ",
+        "      XXXXXX X  XXX X",
+        "   XX   X   XX X XXX ",
+        "X     XXX   XX X XXXX",
+        " XXXXX XXX X    X    ",
+        "XXX XXXXXXXX  X XXXX ",
+        "        X   X  X  XXX",
+        "XXXXXXX X   X  X XXX ",
+        "X     X  X X X X     ",
+        "X XXX X  XXX XXX XX  ",
+        "X XXX X     X  X X   ",
+        "X XXX X     X XX X X ",
+        "X     X  X X    X    ",
+        "XXXXXXX  XXX  X  XX  ",
+    ];
+
+    fn model1_module(x: usize, y: usize) -> bool {
+        MODEL1_ROWS[y].as_bytes()[x] == b'X'
+    }
+
+    /// Whether a module of a 21x21 grid holds format information
+    fn format_module(x: usize, y: usize) -> bool {
+        (x == 8 && y != 6 && (y <= 8 || y >= 14)) || (y == 8 && x != 6 && (x <= 8 || x >= 13))
+    }
+
+    #[test]
+    fn test_model1_grid() {
+        let grid = crate::SimpleGrid::from_func(21, model1_module);
+
+        let mut raw = Vec::new();
+        let meta = decode(&grid, &mut raw).unwrap();
+        assert_eq!(meta.kind, SymbolKind::Model1);
+        assert_eq!(meta.version, Version(1));
+        assert_eq!(meta.ecc_level, 1);
+        assert_eq!(meta.symbology_identifier(), "]Q0");
+        assert_eq!(raw, b"Model 1");
+    }
+
+    #[test]
+    fn test_error_locations() {
+        let damaged = [(3, 12), (8, 18)];
+        let module = |y: usize, x: usize| rmqr_module(x, y) ^ damaged.contains(&(y, x));
+
+        let grid = crate::SimpleGrid::from_func_rect(27, 11, |x, y| module(y, x));
+        let (meta, corrected) = error_locations(&grid).unwrap();
+        assert_eq!(meta.ecc_blocks[0].corrected, 2);
+        assert_eq!(corrected.len(), 2);
+        for damage in damaged {
+            assert!(corrected.iter().any(|c| c.modules.contains(&damage)));
+        }
+        assert!(corrected.iter().all(|c| c.modules.len() == 8));
+
+        // Locations refer to the original grid, even if it was read mirrored
+        let grid = crate::SimpleGrid::from_func_rect(11, 27, module);
+        let (_, mirrored) = error_locations(&grid).unwrap();
+        let transposed: Vec<_> = corrected
+            .into_iter()
+            .map(|codeword| CorrectedCodeword {
+                index: codeword.index,
+                modules: codeword.modules.iter().map(|&(y, x)| (x, y)).collect(),
+            })
+            .collect();
+        assert_eq!(mirrored, transposed);
+    }
+
+    #[test]
+    fn test_scratch_size() {
+        let tables = [
+            &VERSION_DATA_BASE[..],
+            &MODEL1_VERSION_DATA_BASE[..],
+            &MICRO_VERSION_DATA_BASE[..],
+            &RMQR_VERSION_DATA_BASE[..],
+        ];
+        let largest = tables
+            .iter()
+            .flat_map(|table| table.iter())
+            .map(|info| scratch_len(info.data_bytes))
+            .max();
+        assert_eq!(largest, Some(MAX_SCRATCH_SIZE));
+
+        assert_eq!(scratch_size(21, 21), scratch_len(26));
+        assert_eq!(scratch_size(11, 11), scratch_len(5));
+        assert_eq!(scratch_size(27, 11), scratch_size(11, 27));
+        assert_eq!(scratch_size(12, 12), 0);
+
+        // Version 1 Model 1 codes have the same number of codewords
+        let grid = crate::SimpleGrid::encode(b"rqrr", 0).unwrap();
+        let mut buf = [0; scratch_len(26)];
+        let mut out = Vec::new();
+        decode_with_scratch(&grid, &mut buf, &mut out).unwrap();
+        assert_eq!(out, b"rqrr");
+        let mut out = Vec::new();
+        let err = decode_with_scratch(&grid, &mut buf[1..], &mut out);
+        assert_eq!(err.unwrap_err(), DeQRError::BufferTooSmall);
+
+        // The scratch buffer is reused between attempts to recover the format
+        let damaged = [
+            (0, 8),
+            (2, 8),
+            (8, 1),
+            (8, 4),
+            (20, 8),
+            (17, 8),
+            (8, 15),
+            (8, 19),
+        ];
+        let grid =
+            crate::SimpleGrid::from_func(21, |x, y| grid.bit(y, x) ^ damaged.contains(&(y, x)));
+        assert_eq!(read_format(&grid).err(), Some(DeQRError::FormatEcc));
+        let mut out = Vec::new();
+        let meta = decode_with_scratch(&grid, &mut buf, &mut out).unwrap();
+        assert_eq!(out, b"rqrr");
+        assert!(meta.ecc_blocks.is_empty());
+    }
+
+    #[test]
+    fn test_decode_partial() {
+        let meta = symbol_metadata(SymbolKind::Qr, Version(1), 0, 0).unwrap();
+        // Byte mode "AB", followed by the unknown mode indicator 6
+        let mut codewords = [0; 16];
+        codewords[..4].copy_from_slice(&[0x40, 0x24, 0x14, 0x26]);
+        let grid = crate::encode::encode_codewords(&codewords, &meta);
+
+        let partial = decode_partial(&grid).unwrap();
+        assert_eq!(partial.segments.len(), 1);
+        assert_eq!(partial.segments[0].mode, Mode::Byte);
+        assert_eq!(partial.segments[0].data, b"AB");
+        let error = PayloadError {
+            error: DeQRError::UnknownDataType,
+            bit_offset: 28,
+        };
+        assert_eq!(partial.error, Some(error));
+        assert_eq!(
+            decode(&grid, Vec::new()).err(),
+            Some(DeQRError::UnknownDataType)
+        );
+
+        // Byte mode with 20 characters, more than there is data left. The
+        // incomplete segment is dropped.
+        codewords[3] = 0x24;
+        codewords[4] = 0x14;
+        let grid = crate::encode::encode_codewords(&codewords, &meta);
+        let partial = decode_partial(&grid).unwrap();
+        assert_eq!(partial.segments.len(), 1);
+        assert_eq!(partial.segments[0].data, b"AB");
+        let error = PayloadError {
+            error: DeQRError::DataUnderflow,
+            bit_offset: 28,
+        };
+        assert_eq!(partial.error, Some(error));
+
+        // Complete data has no error
+        let grid = crate::SimpleGrid::encode(b"rqrr", 0).unwrap();
+        let partial = decode_partial(&grid).unwrap();
+        assert_eq!(partial.error, None);
+        assert_eq!(partial.segments.len(), 1);
+    }
+
+    #[test]
+    fn test_decode_unknown_eci() {
+        // ECI 899 (binary data), followed by UTF-8 in byte mode
+        let text = "Grüße";
+        let mut fields = vec![(7, 4), (0x8383, 16), (4, 4), (text.len(), 8)];
+        fields.extend(text.bytes().map(|b| (b as usize, 8)));
+        fields.push((0, 4));
+        let mut codewords = Fields::new(&fields).data;
+        for i in 0..16 - codewords.len() {
+            codewords.push([0xec, 0x11][i % 2]);
+        }
+        let meta = symbol_metadata(SymbolKind::Qr, Version(1), 0, 0).unwrap();
+        let grid = crate::encode::encode_codewords(&codewords, &meta);
+
+        let (meta, content) = crate::Grid::new(grid).decode().unwrap();
+        assert_eq!(content, text);
+        assert_eq!(meta.eci, [899]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let meta = symbol_metadata(SymbolKind::Qr, Version(1), 0, 0).unwrap();
+        let check = |data: &[u8]| {
+            // Fill the rest with the alternating pad codewords
+            let mut codewords = [0; 16];
+            codewords[..data.len()].copy_from_slice(data);
+            for (i, pad) in codewords[data.len()..].iter_mut().enumerate() {
+                *pad = [0xec, 0x11][i % 2];
+            }
+            let grid = crate::encode::encode_codewords(&codewords, &meta);
+            validate(&grid).unwrap().1
+        };
+
+        for data in [&b"rqrr"[..], b"https://github.com/WanzenBug/rqrr"] {
+            let grid = crate::SimpleGrid::encode(data, 2).unwrap();
+            assert_eq!(validate(&grid).unwrap().1, []);
+        }
+        // Byte mode "AB", with terminator
+        assert_eq!(check(&[0x40, 0x24, 0x14, 0x20]), []);
+
+        // Pad codewords swapped
+        assert_eq!(
+            check(&[0x40, 0x24, 0x14, 0x20, 0x11, 0xec]),
+            [
+                Violation::PadCodeword {
+                    bit_offset: 32,
+                    found: 0x11,
+                    expected: 0xec,
+                },
+                Violation::PadCodeword {
+                    bit_offset: 40,
+                    found: 0xec,
+                    expected: 0x11,
+                },
+            ]
+        );
+
+        // Numeric mode "1", with a bit set after the terminator
+        assert_eq!(check(&[0x10, 0x04, 0x40]), []);
+        assert_eq!(
+            check(&[0x10, 0x04, 0x41]),
+            [Violation::PadBits { bit_offset: 22 }]
+        );
+
+        // Numeric mode with the value 1000 for three digits
+        assert_eq!(
+            check(&[0x10, 0x0f, 0xe8, 0x00]),
+            [Violation::NumericOverflow {
+                bit_offset: 14,
+                value: 1000,
+            }]
+        );
+
+        // Alphanumeric mode with the value 45 for a single character
+        assert_eq!(
+            check(&[0x20, 0x0d, 0xa0]),
+            [Violation::AlphanumericOverflow {
+                bit_offset: 13,
+                value: 45,
+            }]
+        );
+
+        // Byte mode with 15 characters, more than there is data left
+        let mut data = [0; 16];
+        data[..2].copy_from_slice(&[0x40, 0xf0]);
+        assert_eq!(
+            check(&data),
+            [Violation::CountOverflow {
+                bit_offset: 0,
+                count: 15,
+            }]
+        );
+
+        // Errors in the data stream end the list
+        assert_eq!(
+            check(&[0x40, 0x24, 0x14, 0x26]),
+            [Violation::Payload(PayloadError {
+                error: DeQRError::UnknownDataType,
+                bit_offset: 28,
+            })]
+        );
+    }
+
+    #[test]
+    fn test_validate_short_terminator() {
+        // Numeric mode with 10 digits leaves a single bit of the 5 bit
+        // terminator of M2, which must still be zero
+        let mut meta = symbol_metadata(SymbolKind::Micro, Version(2), 0, 0).unwrap();
+        for (last, expected) in [
+            (0, &[][..]),
+            (1, &[Violation::MissingTerminator { bit_offset: 39 }]),
+        ] {
+            let fields = Fields::new(&[(0, 1), (10, 4), (0, 30), (0, 4), (last, 1)]);
+            let ds = fields.stream();
+            let mut collector = ViolationCollector {
+                violations: Vec::new(),
+            };
+            decode_payload(&mut meta, ds, &mut collector, true).unwrap();
+            assert_eq!(collector.violations, expected);
+        }
+    }
+
+    #[test]
+    fn test_validate_remainder_bits() {
+        // Versions 2 to 6 have 7 remainder bits
+        let grid = crate::SimpleGrid::encode(b"https://github.com/WanzenBug/rqrr", 0).unwrap();
+        let (meta, violations) = validate(&grid).unwrap();
+        assert_eq!(meta.version, Version(3));
+        assert_eq!(violations, []);
+
+        let mut remainder = Vec::new();
+        walk_data(&grid, &meta, |bit, y, x| {
+            if bit >= version_info(&meta).data_bytes * 8 {
+                remainder.push((y, x));
+            }
+        });
+        assert_eq!(remainder.len(), 7);
+
+        let (y, x) = remainder[3];
+        let flipped = crate::SimpleGrid::from_func(grid.size(), |gx, gy| {
+            grid.bit(gy, gx) != (gy == y && gx == x)
+        });
+        let (_, violations) = validate(&flipped).unwrap();
+        assert_eq!(violations, [Violation::RemainderBit { y, x }]);
+    }
+
+    #[test]
+    fn test_mirrored() {
+        for (data, ecc_level) in [(&b"rqrr"[..], 0), (b"https://github.com/WanzenBug/rqrr", 2)] {
+            let grid = crate::SimpleGrid::encode(data, ecc_level).unwrap();
+            let transposed = crate::SimpleGrid::from_func(grid.size(), |x, y| grid.bit(x, y));
+            assert!(!is_mirrored(&grid));
+            assert!(is_mirrored(&transposed));
+
+            let mut out = Vec::new();
+            assert!(!decode(&grid, &mut out).unwrap().mirrored);
+            let mut mirrored = Vec::new();
+            assert!(decode(&transposed, &mut mirrored).unwrap().mirrored);
+            assert_eq!(mirrored, out);
+        }
+
+        // A readable mirrored format is used before brute forcing the format
+        // of the grid as it is
+        for ecc_level in 0..4 {
+            let grid = crate::SimpleGrid::encode(b"rqrr", ecc_level).unwrap();
+            let transposed = crate::SimpleGrid::from_func(grid.size(), |x, y| grid.bit(x, y));
+            let meta = decode(&transposed, Vec::new()).unwrap();
+            assert!(meta.mirrored);
+            assert_eq!(meta.ecc_level, ecc_level);
+        }
+    }
+
+    #[test]
+    fn test_recover_format() {
+        // Four errors in each copy of the format information are too many to
+        // correct them on their own
+        let damaged = [
+            (0, 8),
+            (2, 8),
+            (8, 1),
+            (8, 4),
+            (20, 8),
+            (17, 8),
+            (8, 15),
+            (8, 19),
+        ];
+        let grid = crate::SimpleGrid::from_func(21, |x, y| {
+            model1_module(x, y) ^ damaged.contains(&(y, x))
+        });
+        assert_eq!(read_model1_format(&grid).err(), Some(DeQRError::FormatEcc));
+
+        let mut raw = Vec::new();
+        let meta = decode(&grid, &mut raw).unwrap();
+        assert_eq!(meta.kind, SymbolKind::Model1);
+        assert_eq!(meta.ecc_level, 1);
+        assert_eq!(raw, b"Model 1");
+
+        // Arbitrary format bits are too far from any format to try it
+        let random =
+            |x: usize, y: usize| (x * 31 + y * 17 + 4).wrapping_mul(2654435761) >> 13 & 1 == 1;
+        let grid = crate::SimpleGrid::from_func(21, |x, y| match format_module(x, y) {
+            true => random(x, y),
+            false => model1_module(x, y),
+        });
+        assert_eq!(read_model1_format(&grid).err(), Some(DeQRError::FormatEcc));
+        assert_eq!(read_format(&grid).err(), Some(DeQRError::FormatEcc));
+        assert_eq!(decode(&grid, Vec::new()).err(), Some(DeQRError::FormatEcc));
+    }
+
+    #[test]
+    fn test_decode_hints() {
+        // Clear the format information next to the finder
+        let grid = crate::SimpleGrid::from_func_rect(27, 11, |x, y| {
+            rmqr_module(x, y) && !((8..11).contains(&x) && (1..6).contains(&y))
+        });
+        let (meta, _) = crate::Grid::new(crate::SimpleGrid::from_func_rect(27, 11, rmqr_module))
+            .decode()
+            .unwrap();
+
+        let mut sink = SegmentCollector::new();
+        let hinted = decode_with_hints_into(&grid, &(&meta).into(), &mut sink).unwrap();
+        assert_eq!(hinted.version, meta.version);
+        assert_eq!(sink.segments[0].data, b"ABCDEF");
+
+        // Hints must describe a valid symbol of the grid's size
+        let hints = DecodeHints {
+            version: Some(Version(2)),
+            ..(&meta).into()
+        };
+        let mut sink = SegmentCollector::new();
+        let err = decode_with_hints_into(&grid, &hints, &mut sink).unwrap_err();
+        assert_eq!(err, DeQRError::InvalidVersion);
+        let hints = DecodeHints {
+            mask: Some(0),
+            ..(&meta).into()
+        };
+        let err = decode_with_hints_into(&grid, &hints, &mut sink).unwrap_err();
+        assert_eq!(err, DeQRError::FormatEcc);
+    }
+
+    #[test]
+    fn test_decode_partial_hints() {
+        // Both copies of the format information are wiped
+        let grid =
+            crate::SimpleGrid::from_func(21, |x, y| model1_module(x, y) && !format_module(x, y));
+        let mut raw = Vec::new();
+        assert!(decode(&grid, &mut raw).is_err());
+
+        // All masks are tried for the known kind and error correction level
+        let hints = DecodeHints {
+            kind: Some(SymbolKind::Model1),
+            ecc_level: Some(1),
+            ..Default::default()
+        };
+        let mut sink = SegmentCollector::new();
+        let meta = decode_with_hints_into(&grid, &hints, &mut sink).unwrap();
+        assert_eq!(meta.kind, SymbolKind::Model1);
+        assert_eq!(sink.segments[0].data, b"Model 1");
+    }
+
+    #[test]
+    fn test_bch_15_5() {
+        // Format information of level M, mask 5 before masking
+        assert_eq!(bch_15_5(0b00101), 0b001010011011100);
+        for fdata in 0..32 {
+            assert_eq!(correct_format(bch_15_5(fdata)), Ok(bch_15_5(fdata)));
+        }
+    }
+
+    #[test]
+    fn test_correct_block_erasures() {
+        const BLOCK: [u8; 26] = [
+            114, 113, 114, 114, 32, 101, 114, 97, 115, 117, 114, 101, 32, 82, 83, 33, 104, 227,
+            217, 254, 148, 45, 1, 217, 171, 34,
+        ];
+        let ecc = RSParameters {
+            bs: 26,
+            dw: 16,
+            ns: 1,
+        };
+        let corrupt = |positions: &[usize]| {
+            let mut block = BLOCK;
+            for &i in positions {
+                block[i] ^= 0x5a;
+            }
+            block
+        };
+
+        // 9 errors are too many to correct without erasures
+        let mut block = corrupt(&[0, 2, 4, 6, 8, 10, 12, 18, 24]);
+        assert_eq!(
+            correct_block(&mut block, &ecc, &[]),
+            Err(DeQRError::DataEcc)
+        );
+        let mut block = corrupt(&[0, 2, 4, 6, 8, 10, 12, 18, 24]);
+        let stats = correct_block(&mut block, &ecc, &[0, 2, 4, 6, 8, 10, 12, 18, 24]).unwrap();
+        assert_eq!(block, BLOCK);
+        assert_eq!((stats.corrected, stats.used, stats.unused()), (9, 9, 1));
+
+        // Errors and erasures mixed
+        let mut block = corrupt(&[1, 3, 5, 7, 20, 22, 25]);
+        let stats = correct_block(&mut block, &ecc, &[1, 3, 5, 7]).unwrap();
+        assert_eq!(block, BLOCK);
+        assert_eq!((stats.corrected, stats.used, stats.unused()), (7, 10, 0));
+
+        // Erasures not covering the errors are ignored
+        let mut block = corrupt(&[1, 14, 15]);
+        let stats = correct_block(&mut block, &ecc, &[2, 3, 4, 5, 6, 7, 8, 9]).unwrap();
+        assert_eq!(block, BLOCK);
+        assert_eq!(
+            (stats.corrected, stats.used, stats.remaining_errors()),
+            (3, 6, 2)
+        );
+
+        // Erasures that were not wrong still take up parity
+        let mut block = corrupt(&[1]);
+        let stats = correct_block(&mut block, &ecc, &[1, 2]).unwrap();
+        assert_eq!(block, BLOCK);
+        assert_eq!((stats.corrected, stats.used), (1, 2));
+
+        let mut block = BLOCK;
+        let stats = correct_block(&mut block, &ecc, &[]).unwrap();
+        assert_eq!(stats.data_codewords, 16);
+        assert_eq!(stats.ecc_codewords, 10);
+        assert_eq!((stats.corrected, stats.used), (0, 0));
+    }
 
     #[test]
     fn test_mask_0() {
@@ -756,6 +3627,7 @@ mod tests {
             [1, 0, 1, 0, 1, 0, 1],
         ];
 
+        #[allow(clippy::needless_range_loop)]
         for x in 0..7 {
             for y in 0..7 {
                 assert_eq!(test[y][x] != 0, mask_bit(0, y, x));
@@ -775,6 +3647,7 @@ mod tests {
             [1, 1, 1, 1, 1, 1, 1],
         ];
 
+        #[allow(clippy::needless_range_loop)]
         for x in 0..7 {
             for y in 0..7 {
                 assert_eq!(test[y][x] != 0, mask_bit(1, y, x));
@@ -794,6 +3667,7 @@ mod tests {
             [1, 0, 0, 1, 0, 0, 1],
         ];
 
+        #[allow(clippy::needless_range_loop)]
         for x in 0..7 {
             for y in 0..7 {
                 assert_eq!(test[y][x] != 0, mask_bit(2, y, x));
@@ -813,6 +3687,7 @@ mod tests {
             [1, 0, 0, 1, 0, 0, 1],
         ];
 
+        #[allow(clippy::needless_range_loop)]
         for x in 0..7 {
             for y in 0..7 {
                 assert_eq!(test[y][x] != 0, mask_bit(3, y, x));
@@ -832,6 +3707,7 @@ mod tests {
             [0, 0, 0, 1, 1, 1, 0],
         ];
 
+        #[allow(clippy::needless_range_loop)]
         for x in 0..7 {
             for y in 0..7 {
                 assert_eq!(test[y][x] != 0, mask_bit(4, y, x));
@@ -851,6 +3727,7 @@ mod tests {
             [1, 1, 1, 1, 1, 1, 1],
         ];
 
+        #[allow(clippy::needless_range_loop)]
         for x in 0..7 {
             for y in 0..7 {
                 assert_eq!(test[y][x] != 0, mask_bit(5, y, x));
@@ -870,6 +3747,7 @@ mod tests {
             [1, 1, 1, 1, 1, 1, 1],
         ];
 
+        #[allow(clippy::needless_range_loop)]
         for x in 0..7 {
             for y in 0..7 {
                 assert_eq!(test[y][x] != 0, mask_bit(6, y, x));
@@ -889,6 +3767,7 @@ mod tests {
             [1, 0, 1, 0, 1, 0, 1],
         ];
 
+        #[allow(clippy::needless_range_loop)]
         for x in 0..7 {
             for y in 0..7 {
                 assert_eq!(test[y][x] != 0, mask_bit(7, y, x));
@@ -1,3 +1,5 @@
+use alloc::vec::Vec;
+
 use crate::prepare::{AreaFiller, ImageBuffer, PixelColor};
 use crate::{
     geometry::Perspective,
@@ -0,0 +1,479 @@
+//! EMVCo merchant-presented payment codes
+//!
+//! Payment codes following the EMV QR Code Specification for Payment Systems,
+//! Merchant-Presented Mode (MPM), hold a sequence of data objects. Each object
+//! is a two digit ID, a two digit length and its value, the length counting
+//! characters. Some objects are templates, whose value is again a sequence of
+//! data objects. The last object, ID 63, is a CRC-16/CCITT checksum of all
+//! data before its value.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::error::Error;
+
+/// ID of the payload format indicator, the first object
+const ID_FORMAT: u8 = 0;
+/// ID of the point of initiation method
+const ID_INITIATION: u8 = 1;
+/// IDs of merchant account information, templates from 26 on
+const IDS_MERCHANT_ACCOUNT: core::ops::RangeInclusive<u8> = 2..=51;
+/// ID of the merchant category code
+const ID_CATEGORY: u8 = 52;
+/// ID of the transaction currency
+const ID_CURRENCY: u8 = 53;
+/// ID of the transaction amount
+const ID_AMOUNT: u8 = 54;
+/// ID of the country code
+const ID_COUNTRY: u8 = 58;
+/// ID of the merchant name
+const ID_NAME: u8 = 59;
+/// ID of the merchant city
+const ID_CITY: u8 = 60;
+/// ID of the checksum, the last object
+const ID_CRC: u8 = 63;
+
+/// A data object of a payment code
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EmvObject {
+    /// The ID, between 0 and 99
+    pub id: u8,
+    /// The value, including the data objects of a template
+    pub value: String,
+    /// The data objects of a template, empty for other objects
+    pub children: Vec<EmvObject>,
+}
+
+impl EmvObject {
+    /// Find the data object of a template with the given ID
+    pub fn child(&self, id: u8) -> Option<&EmvObject> {
+        self.children.iter().find(|child| child.id == id)
+    }
+}
+
+/// A merchant-presented payment code
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EmvPayment {
+    /// All data objects in the order of the code, including the checksum
+    pub objects: Vec<EmvObject>,
+}
+
+impl EmvPayment {
+    /// Parse and verify the content of a payment code
+    ///
+    /// Besides the structure and the checksum, the mandatory data objects and
+    /// the format of the values exposed by this type are checked.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let payment = rqrr::EmvPayment::parse(
+    ///     "00020101021229300012D156000000000510A93FO3230Q31280012D15600000001030812345678\
+    ///      520441115802CN5914BEST TRANSPORT6007BEIJING64200002ZH0104最佳运输0202北京\
+    ///      540523.7253031565502016233030412340603***0708A60086670902ME\
+    ///      91320016A0112233449988770708123456786304A13A",
+    /// )?;
+    /// assert_eq!(payment.currency(), "156");
+    /// assert_eq!(payment.amount(), Some("23.72"));
+    /// assert_eq!(payment.country(), "CN");
+    /// assert_eq!(payment.merchant_name(), "BEST TRANSPORT");
+    /// let account = payment.merchant_accounts().next().unwrap();
+    /// assert_eq!(account.child(0).unwrap().value, "D15600000000");
+    ///
+    /// // The checksum does not match if anything is changed
+    /// let tampered = "00020101021229300012D156000000000510A93FO3230Q31280012D15600000001030812345678\
+    ///     520441115802CN5914BEST TRANSPORT6007BEIJING64200002ZH0104最佳运输0202北京\
+    ///     540593.7253031565502016233030412340603***0708A60086670902ME\
+    ///     91320016A0112233449988770708123456786304A13A";
+    /// assert!(matches!(
+    ///     rqrr::EmvPayment::parse(tampered),
+    ///     Err(rqrr::EmvError::Crc { .. })
+    /// ));
+    /// # Ok::<(), rqrr::EmvError>(())
+    /// ```
+    pub fn parse(data: &str) -> Result<Self, EmvError> {
+        let objects = parse_objects(data, 0, true)?;
+        verify_crc(data, &objects)?;
+
+        let payment = EmvPayment { objects };
+        payment.validate()?;
+        Ok(payment)
+    }
+
+    /// Find the top level data object with the given ID
+    pub fn get(&self, id: u8) -> Option<&EmvObject> {
+        self.objects.iter().find(|object| object.id == id)
+    }
+
+    /// Whether the code may only be used for a single transaction
+    ///
+    /// Codes are static, and can be paid repeatedly, unless the point of
+    /// initiation method is 12.
+    pub fn is_dynamic(&self) -> bool {
+        self.value(ID_INITIATION) == Some("12")
+    }
+
+    /// Merchant account information, IDs 2 to 51
+    ///
+    /// Objects 2 to 25 are reserved for payment networks and hold the account
+    /// directly, while objects 26 to 51 are templates identifying the
+    /// network by their child 0.
+    pub fn merchant_accounts(&self) -> impl Iterator<Item = &EmvObject> {
+        self.objects
+            .iter()
+            .filter(|object| IDS_MERCHANT_ACCOUNT.contains(&object.id))
+    }
+
+    /// Merchant category code, 4 digits as in ISO 18245
+    pub fn merchant_category(&self) -> &str {
+        self.value(ID_CATEGORY).unwrap_or_default()
+    }
+
+    /// Transaction currency, 3 digits as in ISO 4217
+    pub fn currency(&self) -> &str {
+        self.value(ID_CURRENCY).unwrap_or_default()
+    }
+
+    /// Transaction amount, if fixed by the code, e.g. `"23.72"`
+    pub fn amount(&self) -> Option<&str> {
+        self.value(ID_AMOUNT)
+    }
+
+    /// Country of the merchant, 2 letters as in ISO 3166-1
+    pub fn country(&self) -> &str {
+        self.value(ID_COUNTRY).unwrap_or_default()
+    }
+
+    /// Name of the merchant
+    pub fn merchant_name(&self) -> &str {
+        self.value(ID_NAME).unwrap_or_default()
+    }
+
+    /// City of the merchant
+    pub fn merchant_city(&self) -> &str {
+        self.value(ID_CITY).unwrap_or_default()
+    }
+
+    fn value(&self, id: u8) -> Option<&str> {
+        self.get(id).map(|object| object.value.as_str())
+    }
+
+    /// Check the mandatory data objects and their values
+    fn validate(&self) -> Result<(), EmvError> {
+        for (i, object) in self.objects.iter().enumerate() {
+            if self.objects[..i].iter().any(|other| other.id == object.id) {
+                return Err(EmvError::Duplicate(object.id));
+            }
+        }
+
+        if self.objects.first().map(|object| object.id) != Some(ID_FORMAT) {
+            return Err(EmvError::Missing(ID_FORMAT));
+        }
+        for id in [ID_CATEGORY, ID_CURRENCY, ID_COUNTRY, ID_NAME, ID_CITY] {
+            if self.get(id).is_none() {
+                return Err(EmvError::Missing(id));
+            }
+        }
+        if self.merchant_accounts().next().is_none() {
+            return Err(EmvError::Missing(*IDS_MERCHANT_ACCOUNT.start()));
+        }
+
+        let digits = |value: &str, len: usize| {
+            value.len() == len && value.bytes().all(|b| b.is_ascii_digit())
+        };
+        let valid = |id: u8, value: &str| match id {
+            ID_FORMAT => value == "01",
+            ID_INITIATION => value == "11" || value == "12",
+            ID_CATEGORY => digits(value, 4),
+            ID_CURRENCY => digits(value, 3),
+            ID_AMOUNT => valid_amount(value),
+            ID_COUNTRY => value.len() == 2 && value.bytes().all(|b| b.is_ascii_alphabetic()),
+            _ => true,
+        };
+        match self
+            .objects
+            .iter()
+            .find(|object| !valid(object.id, &object.value))
+        {
+            Some(object) => Err(EmvError::InvalidValue(object.id)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Errors that can happen while parsing a payment code
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EmvError {
+    /// The data object at the given byte offset has no valid ID or length,
+    /// or runs past the end of the data
+    Malformed(usize),
+    /// The mandatory data object with the given ID is missing or not at its
+    /// required position
+    Missing(u8),
+    /// The data object with the given ID occurs more than once
+    Duplicate(u8),
+    /// The value of the data object with the given ID is malformed
+    InvalidValue(u8),
+    /// The checksum of the data does not match the one in the code, so the
+    /// content is corrupted or was tampered with
+    Crc {
+        /// The checksum of the data
+        expected: u16,
+        /// The checksum in the code
+        found: u16,
+    },
+}
+
+impl Error for EmvError {}
+
+impl ::core::fmt::Display for EmvError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EmvError::Malformed(offset) => {
+                write!(f, "Malformed(Invalid data object at offset {})", offset)
+            }
+            EmvError::Missing(id) => write!(f, "Missing(Data object {:02} missing)", id),
+            EmvError::Duplicate(id) => write!(f, "Duplicate(Data object {:02} repeated)", id),
+            EmvError::InvalidValue(id) => {
+                write!(f, "InvalidValue(Value of data object {:02} malformed)", id)
+            }
+            EmvError::Crc { expected, found } => write!(
+                f,
+                "Crc(Checksum {:04X} does not match {:04X}, content corrupt)",
+                found, expected
+            ),
+        }
+    }
+}
+
+/// Whether a top level data object is a template
+fn is_template(id: u8) -> bool {
+    matches!(id, 26..=51 | 62 | 64 | 80..=99)
+}
+
+/// Split data into its data objects
+///
+/// With `top_level`, the values of templates are split into their children
+/// as well. The offset of the data in the code is used for errors.
+fn parse_objects(data: &str, offset: usize, top_level: bool) -> Result<Vec<EmvObject>, EmvError> {
+    let mut objects = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let start = offset + data.len() - rest.len();
+        let malformed = || EmvError::Malformed(start);
+
+        let header = rest.get(..4).ok_or_else(malformed)?;
+        if !header.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(malformed());
+        }
+        let id = header[..2].parse::<u8>().map_err(|_| malformed())?;
+        let len = header[2..].parse::<usize>().map_err(|_| malformed())?;
+
+        // The length counts characters, which may take several bytes
+        let value_data = &rest[4..];
+        let end = match len {
+            0 => return Err(malformed()),
+            _ => value_data
+                .char_indices()
+                .map(|(i, c)| i + c.len_utf8())
+                .nth(len - 1)
+                .ok_or_else(malformed)?,
+        };
+        let value = &value_data[..end];
+
+        let children = match top_level && is_template(id) {
+            true => parse_objects(value, start + 4, false)?,
+            false => Vec::new(),
+        };
+        objects.push(EmvObject {
+            id,
+            value: value.to_string(),
+            children,
+        });
+        rest = &value_data[end..];
+    }
+    Ok(objects)
+}
+
+/// Compare the checksum in the last data object to the one of the data
+fn verify_crc(data: &str, objects: &[EmvObject]) -> Result<(), EmvError> {
+    let crc = match objects.last() {
+        Some(object) if object.id == ID_CRC => object,
+        _ => return Err(EmvError::Missing(ID_CRC)),
+    };
+    let hex = crc.value.as_bytes();
+    if hex.len() != 4 || !hex.iter().all(u8::is_ascii_hexdigit) {
+        return Err(EmvError::InvalidValue(ID_CRC));
+    }
+    let found = u16::from_str_radix(&crc.value, 16).map_err(|_| EmvError::InvalidValue(ID_CRC))?;
+
+    // The checksum covers everything up to its own value
+    let expected = crc16(&data.as_bytes()[..data.len() - 4]);
+    match expected == found {
+        true => Ok(()),
+        false => Err(EmvError::Crc { expected, found }),
+    }
+}
+
+/// CRC-16 with the CCITT polynomial 0x1021, starting at 0xffff
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xffff;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = match crc & 0x8000 {
+                0 => crc << 1,
+                _ => (crc << 1) ^ 0x1021,
+            };
+        }
+    }
+    crc
+}
+
+/// Whether an amount is a decimal number of up to 13 characters, like
+/// `"23.72"`
+fn valid_amount(value: &str) -> bool {
+    let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+    value.len() <= 13
+        && !integer.is_empty()
+        && integer.bytes().all(|b| b.is_ascii_digit())
+        && fraction.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+
+    /// The example of the EMVCo specification
+    const EXAMPLE: &str = "00020101021229300012D156000000000510A93FO3230Q31280012D1560000000103081234567852044111\
+        5802CN5914BEST TRANSPORT6007BEIJING64200002ZH0104最佳运输0202北京540523.72530315655020162330304\
+        12340603***0708A60086670902ME91320016A0112233449988770708123456786304A13A";
+
+    /// Append a correct checksum to the data objects
+    fn with_crc(data: &str) -> String {
+        let data = format!("{}6304", data);
+        format!("{}{:04X}", data, crc16(data.as_bytes()))
+    }
+
+    /// Mandatory data objects, except the format and the merchant account
+    const MERCHANT: &str = "5204581253039785802US5904Cafe6006Boston";
+
+    #[test]
+    fn test_crc16() {
+        assert_eq!(crc16(b"123456789"), 0x29b1);
+    }
+
+    #[test]
+    fn test_example() {
+        let payment = EmvPayment::parse(EXAMPLE).unwrap();
+        assert!(payment.is_dynamic());
+        assert_eq!(payment.merchant_category(), "4111");
+        assert_eq!(payment.merchant_city(), "BEIJING");
+
+        let accounts: Vec<_> = payment.merchant_accounts().collect();
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(accounts[1].id, 31);
+        assert_eq!(accounts[1].child(3).unwrap().value, "12345678");
+
+        // Lengths count characters
+        let language = payment.get(64).unwrap();
+        assert_eq!(language.child(1).unwrap().value, "最佳运输");
+        assert_eq!(language.child(2).unwrap().value, "北京");
+
+        let additional = payment.get(62).unwrap();
+        assert_eq!(additional.children.len(), 4);
+        assert_eq!(additional.child(6).unwrap().value, "***");
+        // Only templates have children
+        assert!(payment.get(54).unwrap().children.is_empty());
+        assert_eq!(payment.objects.last().unwrap().value, "A13A");
+    }
+
+    #[test]
+    fn test_minimal() {
+        let code = with_crc(&format!("00020101021102164111111111111111{}", MERCHANT));
+        let payment = EmvPayment::parse(&code).unwrap();
+        assert!(!payment.is_dynamic());
+        assert_eq!(payment.amount(), None);
+        assert_eq!(payment.currency(), "978");
+        assert_eq!(
+            payment.merchant_accounts().next().unwrap().value,
+            "4111111111111111"
+        );
+        // Reserved merchant accounts are not templates
+        assert!(payment.get(2).unwrap().children.is_empty());
+    }
+
+    #[test]
+    fn test_tampered() {
+        let mut tampered = EXAMPLE.replace("BEST TRANSPORT", "WORST TRANSPRT");
+        assert_eq!(
+            EmvPayment::parse(&tampered),
+            Err(EmvError::Crc {
+                expected: crc16(&tampered.as_bytes()[..tampered.len() - 4]),
+                found: 0xa13a,
+            })
+        );
+
+        tampered.truncate(tampered.len() - 8);
+        assert_eq!(EmvPayment::parse(&tampered), Err(EmvError::Missing(63)));
+        assert_eq!(
+            EmvPayment::parse(&format!("{}6304XYZW", tampered)),
+            Err(EmvError::InvalidValue(63))
+        );
+    }
+
+    #[test]
+    fn test_malformed() {
+        // Length running past the end, and past the end of a template
+        assert_eq!(EmvPayment::parse("000201010"), Err(EmvError::Malformed(6)));
+        assert_eq!(
+            EmvPayment::parse("0002012605000301"),
+            Err(EmvError::Malformed(10))
+        );
+        assert_eq!(EmvPayment::parse("0A0201"), Err(EmvError::Malformed(0)));
+        assert_eq!(
+            EmvPayment::parse("00020101001"),
+            Err(EmvError::Malformed(6))
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let account = "02041234";
+        let code = |data: &str| EmvPayment::parse(&with_crc(data));
+
+        assert_eq!(
+            code(&format!("{}{}", account, MERCHANT)),
+            Err(EmvError::Missing(0))
+        );
+        assert_eq!(
+            code(&format!("000201{}", MERCHANT)),
+            Err(EmvError::Missing(2))
+        );
+        assert_eq!(
+            code(&format!("000201{}5204581253039785802US5904Cafe", account)),
+            Err(EmvError::Missing(60))
+        );
+        assert_eq!(
+            code(&format!("000201{}{}5802DE", account, MERCHANT)),
+            Err(EmvError::Duplicate(58))
+        );
+        assert_eq!(
+            code(&format!("000202{}{}", account, MERCHANT)),
+            Err(EmvError::InvalidValue(0))
+        );
+        assert_eq!(
+            code(&format!("000201{}{}54041.2.", account, MERCHANT)),
+            Err(EmvError::InvalidValue(54))
+        );
+        assert_eq!(
+            code(&format!(
+                "000201{}{}",
+                account,
+                MERCHANT.replace("5802US", "580212")
+            )),
+            Err(EmvError::InvalidValue(58))
+        );
+        assert!(code(&format!("000201{}{}54041.20", account, MERCHANT)).is_ok());
+    }
+}
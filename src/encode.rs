@@ -0,0 +1,610 @@
+//! QR code encoder
+//!
+//! Produces regular (Model 2) QR codes. The encoder shares the version
+//! tables, the data placement and the Reed-Solomon arithmetic with the
+//! decoder, so everything it writes can be read back.
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::ops::Range;
+
+use crate::decode::{
+    bch_15_5, bch_18_6, mask_bit, symbol_metadata, walk_data, MetaData, Mode, SymbolKind, Version,
+    ALPHA_MAP,
+};
+use crate::galois::{GaloisField, GF256};
+use crate::version_db::VERSION_DATA_BASE;
+use crate::{BitGrid, SimpleGrid};
+
+/// Errors that can happen while encoding a QR code
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EncodeError {
+    /// The error correction level is not between 0 and 3
+    InvalidEccLevel,
+    /// The data does not fit into a version 40 code with the given error
+    /// correction level
+    DataTooLong,
+}
+
+impl Error for EncodeError {}
+
+impl ::core::fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let msg = match self {
+            EncodeError::InvalidEccLevel => "InvalidEccLevel(Unknown error correction level)",
+            EncodeError::DataTooLong => "DataTooLong(Data does not fit into any version)",
+        };
+        write!(f, "{}", msg)
+    }
+}
+
+/// Last version using the same character count lengths, for versions 1-9,
+/// 10-26 and 27-40
+const VERSION_CLASSES: [usize; 3] = [9, 26, 40];
+
+/// Modes used for segmentation, Kanji is never chosen
+const MODES: [Mode; 3] = [Mode::Numeric, Mode::Alphanumeric, Mode::Byte];
+
+/// Mode and range of input bytes of each segment
+type Segments = Vec<(Mode, Range<usize>)>;
+
+/// Encode data as the smallest QR code with the given error correction
+/// level
+///
+/// The error correction level uses the same values as
+/// [`MetaData::ecc_level`]. The mask is chosen by the penalty score of the
+/// finished symbol.
+pub(crate) fn encode(data: &[u8], ecc_level: u16) -> Result<SimpleGrid, EncodeError> {
+    if ecc_level >= 4 {
+        return Err(EncodeError::InvalidEccLevel);
+    }
+
+    let (meta, segments) = fit_version(data, ecc_level)?;
+    let codewords = data_codewords(data, &segments, &meta);
+    Ok(encode_codewords(&codewords, &meta))
+}
+
+/// Draw a regular QR code holding the given data codewords
+///
+/// Adds the error correction codewords, and chooses the mask with the
+/// lowest penalty.
+pub(crate) fn encode_codewords(codewords: &[u8], meta: &MetaData) -> SimpleGrid {
+    let stream = interleave_blocks(codewords, meta);
+
+    let mut symbol = Symbol::new(meta.version);
+    symbol.draw_function_patterns();
+    let modules = symbol.place_data(meta, &stream);
+
+    let (_, best) = (0..8)
+        .map(|mask| {
+            let mut masked = symbol.clone();
+            masked.apply_mask(&modules, mask);
+            masked.draw_format(meta.ecc_level, mask);
+            (masked.penalty(), masked)
+        })
+        .min_by_key(|(penalty, _)| *penalty)
+        .expect("there are 8 masks");
+
+    SimpleGrid::from_func(best.size, |x, y| best.bit(y, x))
+}
+
+/// Find the smallest version that can hold the data, along with the
+/// segmentation used for it
+fn fit_version(data: &[u8], ecc_level: u16) -> Result<(MetaData, Segments), EncodeError> {
+    let mut first = 1;
+    for &last in VERSION_CLASSES.iter() {
+        let meta = symbol_metadata(SymbolKind::Qr, Version(last), ecc_level, 0)
+            .map_err(|_| EncodeError::InvalidEccLevel)?;
+        let segments = segment(data, &meta);
+        let bits: usize = segments
+            .iter()
+            .map(|(mode, range)| 4 + mode.count_bits(&meta) + mode.data_bits(range.len()))
+            .sum();
+
+        for version in first..=last {
+            let meta = symbol_metadata(SymbolKind::Qr, Version(version), ecc_level, 0)
+                .map_err(|_| EncodeError::InvalidEccLevel)?;
+            if bits <= data_capacity(&meta) * 8 {
+                return Ok((meta, segments));
+            }
+        }
+        first = last + 1;
+    }
+    Err(EncodeError::DataTooLong)
+}
+
+fn can_encode(mode: Mode, c: u8) -> bool {
+    match mode {
+        Mode::Numeric => c.is_ascii_digit(),
+        Mode::Alphanumeric => ALPHA_MAP.contains(&c),
+        _ => true,
+    }
+}
+
+/// Split the data into segments with the shortest total length
+///
+/// Lengths are tracked in sixths of a bit, so that every mode has a whole
+/// cost per character. Character count lengths are taken from `meta`.
+fn segment(data: &[u8], meta: &MetaData) -> Segments {
+    const CHAR_COST: [usize; 3] = [20, 33, 48];
+    let header = |m: usize| (4 + MODES[m].count_bits(meta)) * 6;
+
+    /* Cheapest encoding of the data so far, ending in each mode, and the
+     * mode of the previous character in that encoding.
+     */
+    let mut cost = [0; 3];
+    let mut prev_mode = vec![[0u8; 3]; data.len()];
+    for (i, &c) in data.iter().enumerate() {
+        let (best_prev, best) = (0..3)
+            .map(|m| (m, cost[m]))
+            .min_by_key(|&(_, cost)| cost)
+            .expect("there are 3 modes");
+
+        let mut next = [usize::MAX; 3];
+        for m in 0..3 {
+            if !can_encode(MODES[m], c) {
+                continue;
+            }
+            let switch = best + header(m) + CHAR_COST[m];
+            let stay = cost[m].saturating_add(CHAR_COST[m]);
+            if i > 0 && stay <= switch {
+                next[m] = stay;
+                prev_mode[i][m] = m as u8;
+            } else {
+                next[m] = switch;
+                prev_mode[i][m] = best_prev as u8;
+            }
+        }
+        cost = next;
+    }
+
+    let mut segments = Vec::new();
+    let Some((mut mode, _)) = (0..3).map(|m| (m, cost[m])).min_by_key(|&(_, cost)| cost) else {
+        return segments;
+    };
+    let mut end = data.len();
+    for i in (0..data.len()).rev() {
+        let prev = prev_mode[i][mode] as usize;
+        if i == 0 || prev != mode {
+            segments.push((MODES[mode], i..end));
+            end = i;
+            mode = prev;
+        }
+    }
+    segments.reverse();
+    segments
+}
+
+/// Number of data codewords of a symbol, over all blocks
+fn data_capacity(meta: &MetaData) -> usize {
+    let ver = &VERSION_DATA_BASE[meta.version.0];
+    let sb_ecc = &ver.ecc[meta.ecc_level as usize];
+    let lb_count = (ver.data_bytes - sb_ecc.bs * sb_ecc.ns) / (sb_ecc.bs + 1);
+    sb_ecc.dw * sb_ecc.ns + (sb_ecc.dw + 1) * lb_count
+}
+
+/// Appends bits to a byte buffer, most significant bit first
+struct BitWriter {
+    data: Vec<u8>,
+    len: usize,
+}
+
+impl BitWriter {
+    fn push(&mut self, value: usize, nbits: usize) {
+        for i in (0..nbits).rev() {
+            if self.len % 8 == 0 {
+                self.data.push(0);
+            }
+            if (value >> i) & 1 != 0 {
+                self.data[self.len / 8] |= 0x80 >> (self.len % 8);
+            }
+            self.len += 1;
+        }
+    }
+}
+
+/// Encode the segments, terminate and pad the result to the data capacity
+fn data_codewords(data: &[u8], segments: &[(Mode, Range<usize>)], meta: &MetaData) -> Vec<u8> {
+    let mut out = BitWriter {
+        data: Vec::new(),
+        len: 0,
+    };
+    for (mode, range) in segments {
+        let chars = &data[range.clone()];
+        let indicator = match mode {
+            Mode::Numeric => 0b0001,
+            Mode::Alphanumeric => 0b0010,
+            _ => 0b0100,
+        };
+        out.push(indicator, 4);
+        out.push(chars.len(), mode.count_bits(meta));
+
+        match mode {
+            Mode::Numeric => {
+                for group in chars.chunks(3) {
+                    let value = group
+                        .iter()
+                        .fold(0, |acc, &c| acc * 10 + (c - b'0') as usize);
+                    out.push(value, mode.data_bits(group.len()));
+                }
+            }
+            Mode::Alphanumeric => {
+                for pair in chars.chunks(2) {
+                    let value = pair.iter().fold(0, |acc, c| {
+                        acc * 45 + ALPHA_MAP.iter().position(|a| a == c).unwrap()
+                    });
+                    out.push(value, mode.data_bits(pair.len()));
+                }
+            }
+            _ => {
+                for &c in chars {
+                    out.push(c as usize, 8);
+                }
+            }
+        }
+    }
+
+    let capacity = data_capacity(meta);
+    let terminator = (capacity * 8 - out.len).min(4);
+    out.push(0, terminator);
+    out.push(0, (8 - out.len % 8) % 8);
+    for pad in [0xec, 0x11].iter().cycle().take(capacity - out.data.len()) {
+        out.push(*pad, 8);
+    }
+    out.data
+}
+
+/// Compute the error correction codewords of a block
+///
+/// The generator polynomial has the roots `GENERATOR^0` to
+/// `GENERATOR^(npar - 1)`, matching the syndromes used while decoding.
+fn block_ecc(data: &[u8], npar: usize) -> Vec<u8> {
+    /* Coefficients of the generator polynomial, highest degree first */
+    let mut generator = vec![GF256::ONE];
+    for i in 0..npar {
+        let root = GF256::GENERATOR.pow(i);
+        let mut next = vec![GF256::ZERO; generator.len() + 1];
+        for (j, &coef) in generator.iter().enumerate() {
+            next[j] += coef;
+            next[j + 1] += coef * root;
+        }
+        generator = next;
+    }
+
+    let mut rem = vec![GF256::ZERO; npar];
+    for &d in data {
+        let factor = GF256(d) + rem[0];
+        rem.remove(0);
+        rem.push(GF256::ZERO);
+        for (r, &g) in rem.iter_mut().zip(&generator[1..]) {
+            *r += g * factor;
+        }
+    }
+    rem.into_iter().map(|r| r.0).collect()
+}
+
+/// Split the data into blocks, add error correction and interleave the
+/// result
+fn interleave_blocks(data: &[u8], meta: &MetaData) -> Vec<u8> {
+    let ver = &VERSION_DATA_BASE[meta.version.0];
+    let sb_ecc = &ver.ecc[meta.ecc_level as usize];
+    let lb_count = (ver.data_bytes - sb_ecc.bs * sb_ecc.ns) / (sb_ecc.bs + 1);
+    let npar = sb_ecc.bs - sb_ecc.dw;
+
+    let mut blocks = Vec::new();
+    let mut rest = data;
+    for i in 0..sb_ecc.ns + lb_count {
+        let dw = if i < sb_ecc.ns {
+            sb_ecc.dw
+        } else {
+            sb_ecc.dw + 1
+        };
+        let (block, tail) = rest.split_at(dw);
+        blocks.push((block, block_ecc(block, npar)));
+        rest = tail;
+    }
+
+    let mut out = Vec::with_capacity(ver.data_bytes);
+    for j in 0..=sb_ecc.dw {
+        out.extend(blocks.iter().filter_map(|(block, _)| block.get(j)));
+    }
+    for j in 0..npar {
+        out.extend(blocks.iter().map(|(_, ecc)| ecc[j]));
+    }
+    out
+}
+
+/// A square symbol under construction
+#[derive(Clone)]
+struct Symbol {
+    size: usize,
+    modules: Vec<bool>,
+    version: Version,
+}
+
+impl BitGrid for Symbol {
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn bit(&self, y: usize, x: usize) -> bool {
+        self.modules[y * self.size + x]
+    }
+}
+
+impl Symbol {
+    fn new(version: Version) -> Self {
+        let size = version.to_size();
+        Symbol {
+            size,
+            modules: vec![false; size * size],
+            version,
+        }
+    }
+
+    fn set(&mut self, y: usize, x: usize, dark: bool) {
+        self.modules[y * self.size + x] = dark;
+    }
+
+    /// Draw finder, timing and alignment patterns, the dark module and the
+    /// version information
+    fn draw_function_patterns(&mut self) {
+        let size = self.size;
+        for (fy, fx) in [(0, 0), (0, size - 7), (size - 7, 0)] {
+            for i in 0..7usize {
+                for j in 0..7usize {
+                    let ring = i.abs_diff(3).max(j.abs_diff(3));
+                    self.set(fy + i, fx + j, ring != 2);
+                }
+            }
+        }
+
+        for i in 8..size - 8 {
+            self.set(6, i, i % 2 == 0);
+            self.set(i, 6, i % 2 == 0);
+        }
+
+        let apat = &VERSION_DATA_BASE[self.version.0].apat;
+        let centers: Vec<usize> = apat.iter().copied().take_while(|&c| c != 0).collect();
+        if let (Some(&first), Some(&last)) = (centers.first(), centers.last()) {
+            for &y in &centers {
+                for &x in &centers {
+                    let near_finder =
+                        (y == first && (x == first || x == last)) || (y == last && x == first);
+                    if near_finder {
+                        continue;
+                    }
+                    for i in 0..5usize {
+                        for j in 0..5usize {
+                            let ring = i.abs_diff(2).max(j.abs_diff(2));
+                            self.set(y + i - 2, x + j - 2, ring != 1);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.set(size - 8, 8, true);
+
+        if self.version.0 >= 7 {
+            let info = bch_18_6(self.version.0 as u32);
+            for i in 0..6 {
+                for j in 0..3 {
+                    let dark = info & (1 << (i * 3 + j)) != 0;
+                    self.set(i, size - 11 + j, dark);
+                    self.set(size - 11 + j, i, dark);
+                }
+            }
+        }
+    }
+
+    /// Write the interleaved codewords, returning the positions of all data
+    /// modules
+    ///
+    /// Remainder bits after the last codeword are left light.
+    fn place_data(&mut self, meta: &MetaData, stream: &[u8]) -> Vec<(usize, usize)> {
+        let mut modules = Vec::new();
+        let layout = Symbol::new(self.version);
+        walk_data(&layout, meta, |bit, y, x| {
+            modules.push((y, x));
+            let dark = stream
+                .get(bit / 8)
+                .is_some_and(|b| b & (0x80 >> (bit % 8)) != 0);
+            self.set(y, x, dark);
+        });
+        modules
+    }
+
+    fn apply_mask(&mut self, modules: &[(usize, usize)], mask: u16) {
+        for &(y, x) in modules {
+            if mask_bit(mask, y, x) {
+                let i = y * self.size + x;
+                self.modules[i] = !self.modules[i];
+            }
+        }
+    }
+
+    /// Write both copies of the format information
+    fn draw_format(&mut self, ecc_level: u16, mask: u16) {
+        const XS: [usize; 15] = [8, 8, 8, 8, 8, 8, 8, 8, 7, 5, 4, 3, 2, 1, 0];
+        const YS: [usize; 15] = [0, 1, 2, 3, 4, 5, 7, 8, 8, 8, 8, 8, 8, 8, 8];
+
+        let size = self.size;
+        let format = bch_15_5((ecc_level << 3) | mask) ^ 0x5412;
+        let bit = |i: usize| format & (1 << i) != 0;
+        for i in 0..15 {
+            self.set(YS[i], XS[i], bit(i));
+        }
+        for i in 0..7 {
+            self.set(size - 1 - i, 8, bit(14 - i));
+        }
+        for i in 0..8 {
+            self.set(8, size - 8 + i, bit(7 - i));
+        }
+    }
+
+    /// Penalty score of the masked symbol, lower is better
+    fn penalty(&self) -> usize {
+        let size = self.size;
+        let mut penalty = 0;
+
+        /* Runs of 5 or more modules of the same colour, and patterns
+         * similar to the finder, in rows and columns.
+         */
+        for transpose in [false, true] {
+            let bit = |i: usize, j: usize| {
+                if transpose {
+                    self.bit(j, i)
+                } else {
+                    self.bit(i, j)
+                }
+            };
+            for i in 0..size {
+                let mut run = 1;
+                for j in 1..size {
+                    if bit(i, j) == bit(i, j - 1) {
+                        run += 1;
+                        continue;
+                    }
+                    if run >= 5 {
+                        penalty += run - 2;
+                    }
+                    run = 1;
+                }
+                if run >= 5 {
+                    penalty += run - 2;
+                }
+
+                const FINDER: [bool; 7] = [true, false, true, true, true, false, true];
+                for j in 0..size.saturating_sub(6) {
+                    if (0..7).any(|k| bit(i, j + k) != FINDER[k]) {
+                        continue;
+                    }
+                    let light = |range: Range<usize>| range.into_iter().all(|k| !bit(i, k));
+                    if (j >= 4 && light(j - 4..j)) || (j + 11 <= size && light(j + 7..j + 11)) {
+                        penalty += 40;
+                    }
+                }
+            }
+        }
+
+        /* 2x2 blocks of the same colour */
+        for y in 1..size {
+            for x in 1..size {
+                let c = self.bit(y, x);
+                if c == self.bit(y - 1, x) && c == self.bit(y, x - 1) && c == self.bit(y - 1, x - 1)
+                {
+                    penalty += 3;
+                }
+            }
+        }
+
+        /* Deviation of the dark proportion from 50%, in steps of 5% */
+        let dark = self.modules.iter().filter(|&&m| m).count();
+        let total = size * size;
+        penalty += (dark * 20).abs_diff(total * 10) / total * 10;
+
+        penalty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Grid;
+
+    fn round_trip(data: &[u8], ecc_level: u16) -> MetaData {
+        let grid = encode(data, ecc_level).unwrap();
+        let mut out = Vec::new();
+        let meta = Grid::new(grid).decode_to(&mut out).unwrap();
+        assert_eq!(out, data);
+        assert_eq!(meta.ecc_level, ecc_level);
+        assert!(meta.ecc_blocks.iter().all(|block| block.corrected == 0));
+        meta
+    }
+
+    #[test]
+    fn test_reference_codewords() {
+        /* Example from ISO/IEC 18004 Annex I: "01234567" as 1-M */
+        let meta = symbol_metadata(SymbolKind::Qr, Version(1), 0, 0).unwrap();
+        let data = b"01234567";
+        let segments = segment(data, &meta);
+        assert_eq!(segments, vec![(Mode::Numeric, 0..8)]);
+
+        let codewords = data_codewords(data, &segments, &meta);
+        assert_eq!(
+            codewords,
+            [
+                0x10, 0x20, 0x0c, 0x56, 0x61, 0x80, 0xec, 0x11, 0xec, 0x11, 0xec, 0x11, 0xec, 0x11,
+                0xec, 0x11
+            ]
+        );
+        assert_eq!(
+            block_ecc(&codewords, 10),
+            [0xa5, 0x24, 0xd4, 0xc1, 0xed, 0x36, 0xc7, 0x87, 0x2c, 0x55]
+        );
+    }
+
+    #[test]
+    fn test_segment() {
+        let meta = symbol_metadata(SymbolKind::Qr, Version(1), 0, 0).unwrap();
+        assert_eq!(segment(b"", &meta), vec![]);
+        assert_eq!(
+            segment(b"HELLO WORLD", &meta),
+            vec![(Mode::Alphanumeric, 0..11)]
+        );
+        assert_eq!(segment(b"hello", &meta), vec![(Mode::Byte, 0..5)]);
+        assert_eq!(
+            segment(b"a12345678901234567890", &meta),
+            vec![(Mode::Byte, 0..1), (Mode::Numeric, 1..21)]
+        );
+        /* Too short to be worth a separate segment */
+        assert_eq!(segment(b"a1b", &meta), vec![(Mode::Byte, 0..3)]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let meta = round_trip(b"HELLO WORLD", 3);
+        assert_eq!(meta.version, Version(1));
+
+        for ecc_level in 0..4 {
+            round_trip(b"https://github.com/WanzenBug/rqrr", ecc_level);
+            round_trip(b"", ecc_level);
+            round_trip(
+                b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./: mixed 1234567",
+                ecc_level,
+            );
+        }
+
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        for ecc_level in 0..4 {
+            round_trip(&data, ecc_level);
+        }
+    }
+
+    #[test]
+    fn test_every_version() {
+        /* Fill each version to capacity, cycling through the error
+         * correction levels. The decoder must not have to correct anything.
+         */
+        for version in 1..=40 {
+            let ecc_level = (version % 4) as u16;
+            let meta = symbol_metadata(SymbolKind::Qr, Version(version), ecc_level, 0).unwrap();
+            let count_bits = Mode::Byte.count_bits(&meta);
+            let len = (data_capacity(&meta) * 8 - 4 - count_bits) / 8;
+            let data: Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+            assert_eq!(round_trip(&data, ecc_level).version, Version(version));
+        }
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        assert_eq!(
+            encode(b"rqrr", 4).unwrap_err(),
+            EncodeError::InvalidEccLevel
+        );
+        assert!(encode(&[0; 2953], 1).is_ok());
+        assert_eq!(encode(&[0; 2954], 1).unwrap_err(), EncodeError::DataTooLong);
+    }
+}
@@ -0,0 +1,186 @@
+//! Arithmetic in the finite fields GF(2^4) and GF(2^8)
+//!
+//! Reed-Solomon codes work on bytes in GF(2^8), the BCH code of the format
+//! information on nibbles in GF(2^4). In both fields, `x` (i.e. 2) generates
+//! all non-zero elements, so multiplication and division are done with tables
+//! of its powers and logarithms.
+
+use core::ops::{Add, AddAssign, Div, Mul, MulAssign};
+
+/// Operations the error correction needs from a field
+pub(crate) trait GaloisField:
+    Copy + Eq + Add<Output = Self> + AddAssign + Mul<Output = Self> + MulAssign + Div<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const GENERATOR: Self;
+
+    /// Raise the element to the given power
+    fn pow(self, p: usize) -> Self;
+}
+
+/// Powers of `x`, repeated once so that the sum of two logarithms can be
+/// looked up directly
+const fn exp_table<const N: usize>(modulus: u16) -> [u8; N] {
+    let order = N / 2;
+    let mut table = [0; N];
+    let mut value: u16 = 1;
+    let mut i = 0;
+    while i < N {
+        table[i] = value as u8;
+        value <<= 1;
+        if value > order as u16 {
+            value ^= modulus;
+        }
+        i += 1;
+    }
+    table
+}
+
+/// Logarithms to the base `x`, the inverse of the first half of `exp`
+const fn log_table<const M: usize>(exp: &[u8]) -> [u8; M] {
+    let mut table = [0; M];
+    let mut i = 0;
+    while i < M - 1 {
+        table[exp[i] as usize] = i as u8;
+        i += 1;
+    }
+    table
+}
+
+macro_rules! galois_field {
+    ($name:ident, $size:expr, $modulus:expr) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub(crate) struct $name(pub u8);
+
+        impl $name {
+            /// Number of non-zero elements
+            const ORDER: usize = $size - 1;
+            const EXP: [u8; 2 * ($size - 1)] = exp_table($modulus);
+            const LOG: [u8; $size] = log_table(&Self::EXP);
+        }
+
+        impl GaloisField for $name {
+            const ZERO: Self = $name(0);
+            const ONE: Self = $name(1);
+            const GENERATOR: Self = $name(2);
+
+            fn pow(self, p: usize) -> Self {
+                if p == 0 {
+                    return Self::ONE;
+                }
+                if self.0 == 0 {
+                    return Self::ZERO;
+                }
+                let log = Self::LOG[self.0 as usize] as usize * (p % Self::ORDER);
+                $name(Self::EXP[log % Self::ORDER])
+            }
+        }
+
+        impl Add for $name {
+            type Output = Self;
+
+            /* Addition and subtraction are both xor in characteristic 2 */
+            #[allow(clippy::suspicious_arithmetic_impl)]
+            fn add(self, rhs: Self) -> Self {
+                $name(self.0 ^ rhs.0)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, rhs: Self) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl Mul for $name {
+            type Output = Self;
+
+            fn mul(self, rhs: Self) -> Self {
+                if self.0 == 0 || rhs.0 == 0 {
+                    return Self::ZERO;
+                }
+                let log = Self::LOG[self.0 as usize] as usize + Self::LOG[rhs.0 as usize] as usize;
+                $name(Self::EXP[log])
+            }
+        }
+
+        impl MulAssign for $name {
+            fn mul_assign(&mut self, rhs: Self) {
+                *self = *self * rhs;
+            }
+        }
+
+        impl Div for $name {
+            type Output = Self;
+
+            fn div(self, rhs: Self) -> Self {
+                assert!(rhs.0 != 0, "division by zero");
+                if self.0 == 0 {
+                    return Self::ZERO;
+                }
+                let log = Self::LOG[self.0 as usize] as usize + Self::ORDER
+                    - Self::LOG[rhs.0 as usize] as usize;
+                $name(Self::EXP[log])
+            }
+        }
+    };
+}
+
+/* Modulus x^4 + x + 1 */
+galois_field!(GF16, 16, 0b1_0011);
+/* Modulus x^8 + x^4 + x^3 + x^2 + 1 */
+galois_field!(GF256, 256, 0b1_0001_1101);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Multiply as polynomials, then reduce by the modulus
+    fn slow_mul(a: u8, b: u8, bits: u32, modulus: u16) -> u8 {
+        let mut product: u16 = 0;
+        for i in 0..bits {
+            if b & (1 << i) != 0 {
+                product ^= (a as u16) << i;
+            }
+        }
+        for i in (bits..2 * bits).rev() {
+            if product & (1 << i) != 0 {
+                product ^= modulus << (i - bits);
+            }
+        }
+        product as u8
+    }
+
+    #[test]
+    fn test_gf256() {
+        for a in 0..=255 {
+            for b in 0..=255 {
+                let product = GF256(a) * GF256(b);
+                assert_eq!(product.0, slow_mul(a, b, 8, 0b1_0001_1101));
+                if b != 0 {
+                    assert_eq!(product / GF256(b), GF256(a));
+                }
+            }
+        }
+        assert_eq!(GF256::GENERATOR.pow(255), GF256::ONE);
+        assert_eq!(GF256::GENERATOR.pow(8), GF256(0b1_1101));
+        assert_eq!(GF256(7).pow(3), GF256(7) * GF256(7) * GF256(7));
+        assert_eq!(GF256::ZERO.pow(0), GF256::ONE);
+    }
+
+    #[test]
+    fn test_gf16() {
+        for a in 0..16 {
+            for b in 0..16 {
+                let product = GF16(a) * GF16(b);
+                assert_eq!(product.0, slow_mul(a, b, 4, 0b1_0011));
+                if b != 0 {
+                    assert_eq!(product / GF16(b), GF16(a));
+                }
+            }
+        }
+        assert_eq!(GF16::GENERATOR.pow(15), GF16::ONE);
+        assert_eq!(GF16::GENERATOR.pow(4), GF16(0b0011));
+    }
+}
@@ -44,13 +44,19 @@ impl Perspective {
         Some(Perspective(c))
     }
 
+    /// Swap the roles of u and v, mapping a transposed grid
+    pub fn transpose(&self) -> Self {
+        let c = &self.0;
+        Perspective([c[1], c[0], c[2], c[4], c[3], c[5], c[7], c[6]])
+    }
+
     pub fn map(&self, u: f64, v: f64) -> Point {
         let den = self.0[6] * u + self.0[7] * v + 1.0f64;
         let x = (self.0[0] * u + self.0[1] * v + self.0[2]) / den;
         let y = (self.0[3] * u + self.0[4] * v + self.0[5]) / den;
 
-        let x = x.round();
-        let y = y.round();
+        let x = libm::round(x);
+        let y = libm::round(y);
 
         assert!(x <= i32::MAX as f64);
         assert!(x >= i32::MIN as f64);
@@ -0,0 +1,293 @@
+//! GS1 element strings
+//!
+//! Codes in FNC1 first position mode contain GS1 element strings: a sequence
+//! of Application Identifiers (AIs), each followed by its value. Values of
+//! AIs with a predefined length are simply concatenated, all other values are
+//! terminated by a GS (0x1d) separator or the end of the data.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::error::Error;
+
+/// The group separator terminating variable length values
+const GS: char = '\x1d';
+
+/// A single Application Identifier and its value
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Gs1Element {
+    /// The Application Identifier, between 2 and 4 digits
+    pub ai: String,
+    /// The value, without any separator
+    pub value: String,
+}
+
+impl Gs1Element {
+    /// Split GS1 formatted data into its element strings
+    ///
+    /// The data is expected as decoded from a code in FNC1 first position
+    /// mode, see [`Fnc1::First`](crate::Fnc1::First). Values of AIs with a
+    /// predefined length are checked for their length, digits, dates and check
+    /// digits.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let elements = rqrr::Gs1Element::parse_all("010950600013435210ABC123\x1d17261231")?;
+    /// assert_eq!(elements.len(), 3);
+    /// assert_eq!(elements[0].ai, "01");
+    /// assert_eq!(elements[0].value, "09506000134352");
+    /// assert_eq!(elements[1].ai, "10");
+    /// assert_eq!(elements[1].value, "ABC123");
+    /// # Ok::<(), rqrr::Gs1Error>(())
+    /// ```
+    pub fn parse_all(data: &str) -> Result<Vec<Self>, Gs1Error> {
+        let mut elements = Vec::new();
+        let mut rest = data;
+        loop {
+            rest = rest.trim_start_matches(GS);
+            if rest.is_empty() {
+                return Ok(elements);
+            }
+
+            let (element, remaining) = parse_element(rest)?;
+            elements.push(element);
+            rest = remaining;
+        }
+    }
+}
+
+/// Errors that can happen while parsing GS1 element strings
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Gs1Error {
+    /// Data does not start with a known Application Identifier
+    UnknownAi(String),
+    /// Value of the given AI is empty or not of the predefined length
+    InvalidLength(String),
+    /// Value of the given AI contains invalid characters or an invalid date
+    InvalidValue(String),
+    /// Check digit of the value of the given AI does not match
+    CheckDigit(String),
+}
+
+impl Error for Gs1Error {}
+
+impl ::core::fmt::Display for Gs1Error {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Gs1Error::UnknownAi(data) => write!(f, "UnknownAi(No known AI in {:?})", data),
+            Gs1Error::InvalidLength(ai) => {
+                write!(f, "InvalidLength(Value of AI {} missing or too short)", ai)
+            }
+            Gs1Error::InvalidValue(ai) => write!(f, "InvalidValue(Value of AI {} malformed)", ai),
+            Gs1Error::CheckDigit(ai) => write!(f, "CheckDigit(Value of AI {} corrupt)", ai),
+        }
+    }
+}
+
+/// Parse the first element string and return the remaining data
+fn parse_element(data: &str) -> Result<(Gs1Element, &str), Gs1Error> {
+    let unknown = || Gs1Error::UnknownAi(data.chars().take(4).collect());
+
+    let prefix = data.get(..2).ok_or_else(unknown)?;
+    let ai_len = ai_length(prefix).ok_or_else(unknown)?;
+    let ai = data.get(..ai_len).ok_or_else(unknown)?;
+    if !ai.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(unknown());
+    }
+    let rest = &data[ai_len..];
+
+    let (value, rest) = match predefined_length(prefix) {
+        Some(len) => {
+            let value = rest
+                .get(..len)
+                .ok_or_else(|| Gs1Error::InvalidLength(ai.to_string()))?;
+            validate_fixed(ai, value)?;
+            (value, &rest[len..])
+        }
+        None => {
+            let end = rest.find(GS).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(Gs1Error::InvalidLength(ai.to_string()));
+            }
+            (&rest[..end], &rest[end..])
+        }
+    };
+
+    let element = Gs1Element {
+        ai: ai.to_string(),
+        value: value.to_string(),
+    };
+    Ok((element, rest))
+}
+
+/// Number of digits of the AIs starting with the given two digits
+fn ai_length(prefix: &str) -> Option<usize> {
+    let len = match prefix {
+        "00" | "01" | "02" | "03" | "04" => 2,
+        "10" | "11" | "12" | "13" | "14" | "15" | "16" | "17" | "18" | "19" => 2,
+        "20" | "21" | "22" | "30" | "37" => 2,
+        "90" | "91" | "92" | "93" | "94" | "95" | "96" | "97" | "98" | "99" => 2,
+        "23" | "24" | "25" | "40" | "41" | "42" | "71" => 3,
+        "31" | "32" | "33" | "34" | "35" | "36" | "39" | "43" => 4,
+        "70" | "72" | "80" | "81" | "82" => 4,
+        _ => return None,
+    };
+    Some(len)
+}
+
+/// Length of the values of AIs with a predefined length
+///
+/// These values are never followed by a separator.
+fn predefined_length(prefix: &str) -> Option<usize> {
+    let len = match prefix {
+        "00" => 18,
+        "01" | "02" | "03" => 14,
+        "04" => 16,
+        "11" | "12" | "13" | "14" | "15" | "16" | "17" | "18" | "19" => 6,
+        "20" => 2,
+        "31" | "32" | "33" | "34" | "35" | "36" => 6,
+        "41" => 13,
+        _ => return None,
+    };
+    Some(len)
+}
+
+/// Check a value of an AI with predefined length
+///
+/// All of them are numeric. Dates must be valid and identification keys must
+/// have a matching check digit.
+fn validate_fixed(ai: &str, value: &str) -> Result<(), Gs1Error> {
+    let digits = value.as_bytes();
+    if !digits.iter().all(|b| b.is_ascii_digit()) {
+        return Err(Gs1Error::InvalidValue(ai.to_string()));
+    }
+
+    match &ai[..2] {
+        "11" | "12" | "13" | "15" | "16" | "17" if !date_valid(digits) => {
+            Err(Gs1Error::InvalidValue(ai.to_string()))
+        }
+        "00" | "01" | "02" | "03" | "41" if !check_digit_matches(digits) => {
+            Err(Gs1Error::CheckDigit(ai.to_string()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check a date in YYMMDD format
+///
+/// A day of 00 stands for the last day of the month. The century is not
+/// encoded, but every year divisible by 4 is a leap year from 1901 to 2099.
+fn date_valid(digits: &[u8]) -> bool {
+    let year = (digits[0] - b'0') * 10 + (digits[1] - b'0');
+    let month = (digits[2] - b'0') * 10 + (digits[3] - b'0');
+    let day = (digits[4] - b'0') * 10 + (digits[5] - b'0');
+    let days = match month {
+        2 if year % 4 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return false,
+    };
+    day <= days
+}
+
+/// Verify the GS1 modulo 10 check digit in the last position
+fn check_digit_matches(digits: &[u8]) -> bool {
+    let (check, body) = digits.split_last().expect("values are never empty");
+    let sum: u32 = body
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| {
+            let weight = if i % 2 == 0 { 3 } else { 1 };
+            u32::from(d - b'0') * weight
+        })
+        .sum();
+    (10 - sum % 10) % 10 == u32::from(check - b'0')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::vec;
+
+    fn element(ai: &str, value: &str) -> Gs1Element {
+        Gs1Element {
+            ai: ai.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_predefined_and_variable() {
+        let elements =
+            Gs1Element::parse_all("00106141411234567897310300125010ABC\x1d21XYZ").unwrap();
+        assert_eq!(
+            elements,
+            vec![
+                element("00", "106141411234567897"),
+                element("3103", "001250"),
+                element("10", "ABC"),
+                element("21", "XYZ"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_separators() {
+        // Separators after predefined length values and at the end are ignored
+        let elements = Gs1Element::parse_all("\x1d0109506000134352\x1d10LOT1\x1d").unwrap();
+        assert_eq!(
+            elements,
+            vec![element("01", "09506000134352"), element("10", "LOT1")]
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            Gs1Element::parse_all("0109506000134353"),
+            Err(Gs1Error::CheckDigit("01".to_string()))
+        );
+        assert_eq!(
+            Gs1Element::parse_all("01095060001343"),
+            Err(Gs1Error::InvalidLength("01".to_string()))
+        );
+        assert_eq!(
+            Gs1Element::parse_all("17261301"),
+            Err(Gs1Error::InvalidValue("17".to_string()))
+        );
+        for date in ["260431", "260229", "240230"] {
+            assert_eq!(
+                Gs1Element::parse_all(&format!("17{date}")),
+                Err(Gs1Error::InvalidValue("17".to_string()))
+            );
+        }
+        for date in ["260430", "240229", "261231", "260200"] {
+            assert!(Gs1Element::parse_all(&format!("17{date}")).is_ok());
+        }
+        assert_eq!(
+            Gs1Element::parse_all("3103ABCDEF"),
+            Err(Gs1Error::InvalidValue("3103".to_string()))
+        );
+        assert_eq!(
+            Gs1Element::parse_all("10\x1d21X"),
+            Err(Gs1Error::InvalidLength("10".to_string()))
+        );
+        assert_eq!(
+            Gs1Element::parse_all("2712"),
+            Err(Gs1Error::UnknownAi("2712".to_string()))
+        );
+        assert_eq!(
+            Gs1Element::parse_all("0"),
+            Err(Gs1Error::UnknownAi("0".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_gln() {
+        let elements = Gs1Element::parse_all("4145412345000013").unwrap();
+        assert_eq!(elements, vec![element("414", "5412345000013")]);
+    }
+}
@@ -1,11 +1,16 @@
-use std::{cmp, mem};
+use alloc::boxed::Box;
+use core::{cmp, mem};
 
 use crate::{
+    decode::{
+        decode_rmqr_format, decode_version_info, is_mirrored, read_version_info,
+        RMQR_FINDER_FORMAT_MASK,
+    },
     geometry,
     identify::match_capstones::CapStoneGroup,
     prepare::PreparedImage,
     prepare::{AreaFiller, ColoredRegion, ImageBuffer, PixelColor, Row},
-    version_db::VERSION_DATA_BASE,
+    version_db::{RMQR_VERSION_DATA_BASE, VERSION_DATA_BASE},
     BitGrid, CapStone, Point,
 };
 
@@ -17,7 +22,12 @@ use crate::{
 #[derive(Debug, Clone)]
 pub struct SkewedGridLocation {
     pub grid_size: usize,
+    /// Height of the grid, only differs from `grid_size` for rMQR codes
+    pub grid_height: usize,
     pub c: geometry::Perspective,
+    /// Whether the perspective was transposed to bring a mirrored code into
+    /// its true orientation
+    pub mirrored: bool,
 }
 
 impl SkewedGridLocation {
@@ -30,7 +40,8 @@ impl SkewedGridLocation {
     /// capstones to determine the grid size.
     ///
     /// For bigger grids this includes searching for an alignment pattern in the
-    /// 4 corner.
+    /// 4 corner. Model 1 codes have no alignment patterns, so the estimated
+    /// position of the corner is kept if it fits better.
     ///
     /// If no sufficient match could be produces, return `None` instead.
     pub fn from_group<S>(img: &mut PreparedImage<S>, mut group: CapStoneGroup) -> Option<Self>
@@ -63,18 +74,27 @@ impl SkewedGridLocation {
         /* Check the timing pattern. This doesn't require a perspective
          * transform.
          */
-        let grid_size = measure_timing_pattern(img, &group);
+        let mut grid_size = measure_timing_pattern(img, &group);
+
+        /* Larger grids also encode their version next to the top right and
+         * bottom left capstones, which is more reliable than counting the
+         * modules of the timing pattern.
+         */
+        if let Some(size) = measure_version_info(img, &group, grid_size) {
+            grid_size = size;
+        }
 
         /* Make an estimate based for the alignment pattern based on extending
          * lines from capstones A and C.
          */
 
-        let mut align = geometry::line_intersect(
+        let estimate = geometry::line_intersect(
             &group.0.corners[0],
             &group.0.corners[1],
             &group.2.corners[0],
             &group.2.corners[3],
         )?;
+        let mut align = estimate;
 
         /* On V2+ grids, we should use the alignment pattern. */
         if grid_size > 21 {
@@ -98,9 +118,208 @@ impl SkewedGridLocation {
             return None;
         }
 
-        let c = setup_perspective(img, &group, align, grid_size)?;
+        let mut initial = initial_perspective(&group, align, grid_size)?;
+
+        /* Model 1 codes have no alignment pattern, anything of the right
+         * size may have been found in its place.
+         */
+        if align != estimate {
+            if let Some(fallback) = initial_perspective(&group, estimate, grid_size) {
+                if fitness_all(img, &fallback, grid_size) > fitness_all(img, &initial, grid_size) {
+                    initial = fallback;
+                }
+            }
+        }
+
+        let c = jiggle_perspective(img, initial, |img, c| fitness_all(img, c, grid_size));
+
+        let location = SkewedGridLocation {
+            grid_size,
+            grid_height: grid_size,
+            c,
+            mirrored: false,
+        };
+        Some(location.orient(img))
+    }
+
+    /// Create a SkewedGridLocation from a single capstone
+    ///
+    /// Micro QR codes only have a capstone in the top-left corner, with timing
+    /// patterns running along the top and left edge of the grid. The capstone
+    /// is tried in every rotation until both timing patterns are found. Their
+    /// length determines the grid size.
+    ///
+    /// Rectangular Micro QR codes (rMQR) also have their only capstone in the
+    /// top-left corner, see [`from_rmqr_capstone`](Self::from_rmqr_capstone).
+    /// They are checked first in every rotation, as their format information
+    /// and sub-finder are much less likely to match by accident than the
+    /// timing patterns of a Micro QR code.
+    ///
+    /// If no (rectangular) Micro QR code could be matched, return `None`
+    /// instead.
+    pub fn from_capstone<S>(img: &mut PreparedImage<S>, mut cap: CapStone) -> Option<Self>
+    where
+        S: ImageBuffer,
+    {
+        for _ in 0..4 {
+            cap.corners.rotate_left(1);
+            let c = geometry::Perspective::create(&cap.corners, 7.0, 7.0)?;
+
+            if let Some(location) = Self::from_rmqr_capstone(img, &cap, &c) {
+                return Some(location);
+            }
+
+            let grid_size = match (
+                measure_micro_timing_pattern(img, &c, true),
+                measure_micro_timing_pattern(img, &c, false),
+            ) {
+                (Some(h), Some(v)) if h == v => h,
+                _ => continue,
+            };
+
+            let c = jiggle_perspective(img, c, |img, c| fitness_micro(img, c, grid_size));
+            let location = SkewedGridLocation {
+                grid_size,
+                grid_height: grid_size,
+                c,
+                mirrored: false,
+            };
+            return Some(location.orient(img));
+        }
+
+        None
+    }
+
+    /// Create a SkewedGridLocation of an rMQR code from its capstone
+    ///
+    /// The capstone needs to be rotated so that the top edge timing pattern
+    /// starts to its right. The format information next to the capstone
+    /// determines width and height of the grid. As the grid may be much wider
+    /// than the capstone, the capstone alone can only give a rough estimate
+    /// of where the sub-finder pattern in the bottom-right corner is. The
+    /// centre of the sub-finder is searched for near that estimate, and only
+    /// accepted if the rings around it match.
+    ///
+    /// The final perspective is fit to the capstone, the sub-finder, the
+    /// corner finders and the timing patterns along the edges.
+    fn from_rmqr_capstone<S>(
+        img: &mut PreparedImage<S>,
+        cap: &CapStone,
+        c: &geometry::Perspective,
+    ) -> Option<Self>
+    where
+        S: ImageBuffer,
+    {
+        /* The timing pattern along the top edge starts next to the capstone */
+        for x in 8..12 {
+            let dark = fitness_cell(img, c, x, 0) > 0;
+            if dark != (x % 2 == 0) {
+                return None;
+            }
+        }
+
+        let mut format = 0;
+        for y in (1..4).rev() {
+            format = (format << 1) | (fitness_cell(img, c, 11, y) > 0) as u32;
+        }
+        for x in (8..11).rev() {
+            for y in (1..6).rev() {
+                format = (format << 1) | (fitness_cell(img, c, x, y) > 0) as u32;
+            }
+        }
+        let (version, _) = decode_rmqr_format(format, RMQR_FINDER_FORMAT_MASK).ok()?;
+        let (width, height) = version.to_rmqr_size();
+
+        /* Module steps along the edges of the capstone */
+        let origin = (cap.corners[0].x as f64, cap.corners[0].y as f64);
+        let step_x = (
+            (cap.corners[1].x - cap.corners[0].x) as f64 / 7.0,
+            (cap.corners[1].y - cap.corners[0].y) as f64 / 7.0,
+        );
+        let step_y = (
+            (cap.corners[3].x - cap.corners[0].x) as f64 / 7.0,
+            (cap.corners[3].y - cap.corners[0].y) as f64 / 7.0,
+        );
+
+        /* Small errors in the capstone add up over the width of the grid */
+        let sub_finder = find_sub_finder_pattern(
+            img,
+            c.map(width as f64 - 2.5, height as f64 - 2.5),
+            step_x,
+            step_y,
+            4 + width / 8,
+        )?;
+
+        /* The capstone is accurate along the short side. Correct the long
+         * side, so the grid passes through the centre of the sub-finder. The
+         * result is an affine mapping, not rounded to any pixel.
+         */
+        let w = width as f64 - 2.5;
+        let h = height as f64 - 2.5;
+        let step_x = (
+            (sub_finder.0 - origin.0 - h * step_y.0) / w,
+            (sub_finder.1 - origin.1 - h * step_y.1) / w,
+        );
+        let initial = geometry::Perspective([
+            step_x.0, step_y.0, origin.0, step_x.1, step_y.1, origin.1, 0.0, 0.0,
+        ]);
+
+        let c = jiggle_perspective(img, initial, |img, c| {
+            fitness_rmqr(img, c, version.0, width, height)
+        });
+        Some(SkewedGridLocation {
+            grid_size: width,
+            grid_height: height,
+            c,
+            mirrored: false,
+        })
+    }
+
+    /// Change the size of a regular grid, keeping its corners in place
+    ///
+    /// The perspective is scaled, so the capstones and the alignment pattern
+    /// are found at the same position in the image, then fit to the new
+    /// size. Returns `None` for sizes no regular grid has.
+    fn resize<S>(&self, img: &PreparedImage<S>, grid_size: usize) -> Option<Self>
+    where
+        S: ImageBuffer,
+    {
+        if grid_size < 21 || version_from_grid_size(grid_size) >= VERSION_DATA_BASE.len() {
+            return None;
+        }
+
+        let scale = (self.grid_size - 7) as f64 / (grid_size - 7) as f64;
+        let mut c = self.c.clone();
+        for i in [0, 1, 3, 4, 6, 7] {
+            c.0[i] *= scale;
+        }
+
+        let c = jiggle_perspective(img, c, |img, c| fitness_all(img, c, grid_size));
+        Some(SkewedGridLocation {
+            grid_size,
+            grid_height: grid_size,
+            c,
+            mirrored: self.mirrored,
+        })
+    }
 
-        Some(SkewedGridLocation { grid_size, c })
+    /// Bring the grid of a mirrored code into its true orientation
+    ///
+    /// The capstones can not tell a mirrored code from a regular one, they
+    /// are always put in clockwise order. Only the format information reads
+    /// correctly when the grid of a mirrored code is transposed.
+    fn orient<S>(self, img: &PreparedImage<S>) -> Self
+    where
+        S: ImageBuffer,
+    {
+        let grid = self.into_grid_image(img);
+        let mirrored = is_mirrored(&grid);
+        let mut location = grid.grid;
+        if mirrored {
+            location.c = location.c.transpose();
+            location.mirrored = !location.mirrored;
+        }
+        location
     }
 
     /// Convert into a grid referencing the underlying image as source
@@ -127,6 +346,14 @@ pub struct RefGridImage<'a, S> {
     img: &'a PreparedImage<S>,
 }
 
+impl<S> RefGridImage<'_, S> {
+    /// Position of the center of the module at the given location in the
+    /// image
+    pub fn module_position(&self, y: usize, x: usize) -> Point {
+        self.grid.c.map(x as f64 + 0.5, y as f64 + 0.5)
+    }
+}
+
 impl<S> BitGrid for RefGridImage<'_, S>
 where
     S: ImageBuffer,
@@ -136,21 +363,43 @@ where
     }
 
     fn bit(&self, y: usize, x: usize) -> bool {
-        let p = self.grid.c.map(x as f64 + 0.5, y as f64 + 0.5);
+        let p = self.module_position(y, x);
         PixelColor::White != self.img.get_pixel_at_point(p)
     }
+
+    /// Modules sampled outside the image or close to the threshold
+    fn uncertain(&self, y: usize, x: usize) -> bool {
+        let p = self.module_position(y, x);
+        self.img.is_uncertain_at_point(p)
+    }
+
+    /// Only regular grids can be re-sampled
+    fn resample(&self, size: usize) -> Option<Box<dyn BitGrid + '_>> {
+        if self.grid.grid_size < 21 || self.grid.grid_size != self.grid.grid_height {
+            return None;
+        }
+        let grid = self.grid.resize(self.img, size)?;
+        Some(Box::new(RefGridImage {
+            grid,
+            img: self.img,
+        }))
+    }
+
+    fn width(&self) -> usize {
+        self.grid.grid_size
+    }
+
+    fn height(&self) -> usize {
+        self.grid.grid_height
+    }
 }
 
-fn setup_perspective<S>(
-    img: &PreparedImage<S>,
+fn initial_perspective(
     caps: &CapStoneGroup,
     align: Point,
     grid_size: usize,
-) -> Option<geometry::Perspective>
-where
-    S: ImageBuffer,
-{
-    let initial = geometry::Perspective::create(
+) -> Option<geometry::Perspective> {
+    geometry::Perspective::create(
         &[
             caps.1.corners[0],
             caps.2.corners[0],
@@ -159,9 +408,7 @@ where
         ],
         (grid_size - 7) as f64,
         (grid_size - 7) as f64,
-    )?;
-
-    Some(jiggle_perspective(img, initial, grid_size))
+    )
 }
 
 fn rotate_capstone(cap: &mut CapStone, h0: &Point, hd: &Point) {
@@ -205,10 +452,91 @@ where
     /* Choose the nearest allowable grid size */
     assert!(scan >= 1);
     let size = scan + 13;
-    let ver = (size as f64 - 15.0).floor() as usize / 4;
+    let ver = libm::floor(size as f64 - 15.0) as usize / 4;
     ver * 4 + 17
 }
 
+/// Read the grid size from the version information blocks
+///
+/// Each block is read relative to the capstone it is next to, so it can be
+/// found even if the size measured on the timing pattern is wrong. The
+/// blocks are read if either the timing pattern or the distance between the
+/// capstones comes close to version 7. If the timing pattern indicates a
+/// version too small to carry version information, only a version both
+/// blocks agree on is accepted.
+///
+/// Returns `None` if no version could be decoded.
+fn measure_version_info<S>(
+    img: &PreparedImage<S>,
+    caps: &CapStoneGroup,
+    grid_size: usize,
+) -> Option<usize>
+where
+    S: ImageBuffer,
+{
+    /* Allow for version 7 codes to be measured as version 6 */
+    if grid_size < 41 && capstone_spacing(caps) < 41.0 {
+        return None;
+    }
+
+    let top_right =
+        read_version_info(|i, j| fitness_cell(img, &caps.2.c, j as i32 - 4, i as i32) > 0);
+    let bottom_left =
+        read_version_info(|i, j| fitness_cell(img, &caps.0.c, i as i32, j as i32 - 4) > 0);
+    let version = match (
+        decode_version_info(top_right),
+        decode_version_info(bottom_left),
+    ) {
+        (Ok(a), Ok(b)) if a == b => a,
+        (Ok(_), Ok(_)) => return None,
+        (Ok(v), Err(_)) | (Err(_), Ok(v)) if grid_size >= 45 => v,
+        _ => return None,
+    };
+    Some(version.to_size())
+}
+
+/// Estimate the grid size from the distance between the capstones
+///
+/// The centers of the other capstones are located in the modules of the top
+/// left capstone. They are 3.5 modules from the far edges of the grid.
+fn capstone_spacing(caps: &CapStoneGroup) -> f64 {
+    let (u, _) = caps.1.c.unmap(&caps.2.center);
+    let (_, v) = caps.1.c.unmap(&caps.0.center);
+    u.max(v) + 3.5
+}
+
+/// Measure a timing pattern of a Micro QR code
+///
+/// Starting after the separator of the capstone, modules along the top (or
+/// left) edge alternate between dark and light. The pattern ends with a dark
+/// module in the last column (or row), followed by the light quiet zone.
+///
+/// Returns the grid size if it matches a Micro QR version.
+fn measure_micro_timing_pattern<S>(
+    img: &PreparedImage<S>,
+    perspective: &geometry::Perspective,
+    horizontal: bool,
+) -> Option<usize>
+where
+    S: ImageBuffer,
+{
+    let mut i = 7;
+    while i < 19 {
+        let (x, y) = if horizontal { (i, 0) } else { (0, i) };
+        let dark = fitness_cell(img, perspective, x, y) > 0;
+        if dark != (i % 2 == 0) {
+            break;
+        }
+        i += 1;
+    }
+
+    // The first quiet zone module still matches the expected light module
+    match i - 1 {
+        size @ (11 | 13 | 15 | 17) => Some(size as usize),
+        _ => None,
+    }
+}
+
 fn timing_scan<S>(img: &PreparedImage<S>, p0: &Point, p1: &Point) -> usize
 where
     S: ImageBuffer,
@@ -289,6 +617,101 @@ where
     None
 }
 
+/// Search the centre of the sub-finder pattern of an rMQR code
+///
+/// Spirals outwards from the estimated position, up to `max_distance`
+/// modules, looking for a region the size of a single module. A region is
+/// accepted when the light and dark rings around it match the sub-finder.
+/// `step_x` and `step_y` are the offsets between neighbouring modules, as
+/// estimated from the capstone.
+///
+/// Returns the centre of the region in image coordinates.
+fn find_sub_finder_pattern<S>(
+    img: &mut PreparedImage<S>,
+    mut seed: Point,
+    step_x: (f64, f64),
+    step_y: (f64, f64),
+    max_distance: usize,
+) -> Option<(f64, f64)>
+where
+    S: ImageBuffer,
+{
+    /* Allow up to 5 of the 25 modules of the pattern to mismatch */
+    const MIN_SCORE: i32 = 9 * (20 - 5);
+    const DX_MAP: [i32; 4] = [1, 0, -1, 0];
+    const DY_MAP: [i32; 4] = [0, -1, 0, 1];
+
+    let area = (step_x.0 * step_y.1 - step_x.1 * step_y.0).abs();
+    let size_estimate = area as usize;
+    let max_step = 2.0 * libm::sqrt(area) * max_distance as f64;
+    let mut dir = 0;
+    let mut step_size = 1;
+
+    while (step_size as f64) < max_step {
+        for _pass in 0..step_size {
+            let (x, y) = (seed.x as usize, seed.y as usize);
+            seed.x += DX_MAP[dir];
+            seed.y += DY_MAP[dir];
+
+            if seed.x < 0 || seed.y < 0 || x >= img.width() || y >= img.height() {
+                continue;
+            }
+            if PixelColor::White == img.get_pixel_at(x, y) {
+                continue;
+            }
+            match img.get_region((x, y)) {
+                ColoredRegion::Unclaimed { pixel_count, .. }
+                    if pixel_count >= size_estimate / 2 && pixel_count <= size_estimate * 2 => {}
+                _ => continue,
+            }
+
+            let centroid =
+                img.repaint_and_apply((x, y), PixelColor::Alignment, CentroidFinder::default());
+            let centre = (centroid.x / centroid.count, centroid.y / centroid.count);
+
+            /* Map the 5x5 modules of the pattern around the centre */
+            let local = geometry::Perspective([
+                step_x.0,
+                step_y.0,
+                centre.0 - 2.5 * (step_x.0 + step_y.0),
+                step_x.1,
+                step_y.1,
+                centre.1 - 2.5 * (step_x.1 + step_y.1),
+                0.0,
+                0.0,
+            ]);
+            if fitness_apat(img, &local, 2, 2) >= MIN_SCORE {
+                return Some(centre);
+            }
+        }
+
+        // Cycle directions
+        dir = (dir + 1) % 4;
+        if dir & 1 == 0 {
+            step_size += 1
+        }
+    }
+    None
+}
+
+/// Computes the centre of all pixels in a region
+#[derive(Default)]
+struct CentroidFinder {
+    x: f64,
+    y: f64,
+    count: f64,
+}
+
+impl AreaFiller for CentroidFinder {
+    fn update(&mut self, row: Row) {
+        let len = (row.right - row.left + 1) as f64;
+        /* Pixel centres are offset by half a pixel */
+        self.x += len * ((row.left + row.right) as f64 / 2.0 + 0.5);
+        self.y += len * (row.y as f64 + 0.5);
+        self.count += len;
+    }
+}
+
 struct LeftMostFinder {
     line_p: Point,
     best: Point,
@@ -314,15 +737,16 @@ impl AreaFiller for LeftMostFinder {
     }
 }
 
-fn jiggle_perspective<S>(
+fn jiggle_perspective<S, F>(
     img: &PreparedImage<S>,
     mut perspective: geometry::Perspective,
-    grid_size: usize,
+    fitness: F,
 ) -> geometry::Perspective
 where
     S: ImageBuffer,
+    F: Fn(&PreparedImage<S>, &geometry::Perspective) -> i32,
 {
-    let mut best = fitness_all(img, &perspective, grid_size);
+    let mut best = fitness(img, &perspective);
     let mut adjustments: [f64; 8] = [
         perspective.0[0] * 0.02f64,
         perspective.0[1] * 0.02f64,
@@ -343,7 +767,7 @@ where
             let new = if i & 1 != 0 { old + step } else { old - step };
 
             perspective.0[j] = new;
-            let test = fitness_all(img, &perspective, grid_size);
+            let test = fitness(img, &perspective);
             if test > best {
                 best = test
             } else {
@@ -403,6 +827,77 @@ where
     score
 }
 
+/* Compute a fitness score for a Micro QR code, which only has a single
+ * capstone and timing patterns along the edges.
+ */
+fn fitness_micro<S>(
+    img: &PreparedImage<S>,
+    perspective: &geometry::Perspective,
+    grid_size: usize,
+) -> i32
+where
+    S: ImageBuffer,
+{
+    let mut score = 0;
+
+    /* Check the timing patterns */
+    for i in 8..(grid_size as i32) {
+        let expect = if 0 != i & 1 { -1 } else { 1 };
+        score += fitness_cell(img, perspective, i, 0) * expect;
+        score += fitness_cell(img, perspective, 0, i) * expect;
+    }
+
+    /* Check capstone */
+    score += fitness_capstone(img, perspective, 0, 0);
+    score
+}
+
+/* Compute a fitness score for an rMQR code, using the capstone, the
+ * sub-finder, the corner finders and the timing patterns along the top and
+ * bottom edges.
+ */
+fn fitness_rmqr<S>(
+    img: &PreparedImage<S>,
+    perspective: &geometry::Perspective,
+    version: usize,
+    width: usize,
+    height: usize,
+) -> i32
+where
+    S: ImageBuffer,
+{
+    let info = &RMQR_VERSION_DATA_BASE[version];
+    let width = width as i32;
+    let height = height as i32;
+    let mut score = 0;
+
+    /* Check the timing patterns. They are interrupted by the vertical timing
+     * patterns and end in a corner finder (top) or the sub-finder (bottom).
+     */
+    for x in 7..width {
+        let column = info
+            .apat
+            .iter()
+            .take_while(|&&a| a != 0)
+            .any(|&a| a as i32 == x);
+        let top = x % 2 == 0 || column || x == width - 2;
+        let bottom = x % 2 == 0 || column || x + 5 >= width;
+        score += fitness_cell(img, perspective, x, 0) * if top { 1 } else { -1 };
+        score += fitness_cell(img, perspective, x, height - 1) * if bottom { 1 } else { -1 };
+    }
+
+    /* Check capstone and sub-finder */
+    score += fitness_capstone(img, perspective, 0, 0);
+    score += fitness_apat(img, perspective, width - 3, height - 3);
+
+    /* Check the inner modules of the corner finders */
+    score += fitness_cell(img, perspective, width - 2, 1);
+    if height > 9 {
+        score += fitness_cell(img, perspective, 1, height - 2);
+    }
+    score
+}
+
 fn fitness_apat<S>(
     img: &PreparedImage<S>,
     perspective: &geometry::Perspective,
@@ -3,7 +3,11 @@
 //! This crates exports functions and types that can be used to search for
 //! QR-Codes in images and decode them.
 //!
+//! The crate supports `no_std` targets with an allocator. Disable the default
+//! `std` feature, which is also required by the `img` feature, to build
+//! without the standard library.
 //!
+#![no_std]
 #![cfg_attr(
     feature = "img",
     doc = r##"
@@ -34,19 +38,45 @@ If you have some other form of picture storage, you can use
 you to define your own source for images.
 "##
 )]
-pub use self::decode::{MetaData, RawData, Version, MAX_PAYLOAD_SIZE};
+pub use self::charset::{CharacterSet, DecodedText};
+pub use self::decode::{
+    BlockStats, CorrectedCodeword, DecodeHints, Fnc1, MetaData, Mode, PartialDecode, PayloadError,
+    RawData, Segment, SymbolKind, Version, Violation, MAX_PAYLOAD_SIZE, MAX_SCRATCH_SIZE,
+};
 pub(crate) use self::detect::{capstones_from_image, CapStone};
+pub use self::emv::{EmvError, EmvObject, EmvPayment};
+pub use self::encode::EncodeError;
+pub use self::gs1::{Gs1Element, Gs1Error};
+pub use self::identify::grid::RefGridImage;
 pub use self::identify::Point;
 pub(crate) use self::identify::SkewedGridLocation;
+pub use self::payload::{Contact, Email, Geo, Otp, OtpKind, Payload, Sms, Wifi};
 pub use self::prepare::PreparedImage;
-use std::error::Error;
-use std::io::Write;
+pub use self::render::{Render, RenderOptions};
+pub use self::structured_append::{Reassembler, StructuredAppend};
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate std;
 
+use alloc::boxed::Box;
+use alloc::string::{FromUtf8Error, String};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::error::Error;
+
+mod charset;
 mod decode;
 mod detect;
+mod emv;
+mod encode;
+mod galois;
 pub(crate) mod geometry;
+mod gs1;
 mod identify;
+mod payload;
 mod prepare;
+mod render;
+mod structured_append;
 mod version_db;
 
 /// Wrapper around any grid that can be interpreted as a QR code
@@ -62,6 +92,10 @@ pub struct Grid<G> {
     /// If this grid references for example an underlying image, these values
     /// will be set to coordinates in that image.
     pub bounds: [Point; 4],
+    /// Whether the grid was found with light and dark swapped
+    inverted: bool,
+    /// Whether the grid was found to be mirrored
+    mirrored: bool,
 }
 
 impl<G> Grid<G>
@@ -80,18 +114,184 @@ where
                 Point { x: 0, y: 0 },
                 Point { x: 0, y: 0 },
             ],
+            inverted: false,
+            mirrored: false,
         }
     }
 
+    /// Whether the grid was found with light and dark swapped, i.e. the code
+    /// has light modules on a dark background.
+    ///
+    /// The bits of the backing grid are already corrected for this, see
+    /// [`PreparedImage::detect_grids_inverted`].
+    pub fn is_inverted(&self) -> bool {
+        self.inverted
+    }
+
+    /// Whether the grid was found to be mirrored, and transposed into the
+    /// true orientation of the code.
+    ///
+    /// The bounds and the bits of the backing grid follow the true
+    /// orientation. The metadata of decoded grids reports mirrored codes
+    /// either way, see [`MetaData::mirrored`].
+    pub fn is_mirrored(&self) -> bool {
+        self.mirrored
+    }
+
     /// Try to decode the grid.
     ///
     /// If successful returns the decoded string as well as metadata about the
-    /// code.
+    /// code. Data following an ECI designator is converted from the designated
+    /// character set to UTF-8. For data without ECI designator, or with an
+    /// ECI that designates no known character set, the character set is
+    /// detected, see [`CharacterSet::guess`].
     pub fn decode(&self) -> DeQRResult<(MetaData, String)> {
-        let mut out = Vec::new();
-        let meta = self.decode_to(&mut out)?;
-        let out = String::from_utf8(out)?;
-        Ok((meta, out))
+        let (meta, decoded) = self.decode_text()?;
+        Ok((meta, decoded.text))
+    }
+
+    /// Try to decode the grid, using known properties of the code.
+    ///
+    /// Works like [`decode`](Self::decode), but the properties given in the
+    /// hints are used instead of reading them from the format information.
+    /// This way, codes with damaged format information can still be decoded.
+    /// If the hinted version does not match the size of the grid, the grid
+    /// is re-sampled at the hinted size.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "img"))]
+    /// # fn main() {}
+    /// # #[cfg(feature = "img")]
+    /// # fn main() -> Result<(), Box<dyn ::std::error::Error>> {
+    /// # let img = image::open("tests/data/github.gif")?.to_luma8();
+    /// let mut img = rqrr::PreparedImage::prepare(img);
+    /// let grids = img.detect_grids();
+    /// let hints = rqrr::DecodeHints {
+    ///     ecc_level: Some(0),
+    ///     mask: Some(0),
+    ///     ..Default::default()
+    /// };
+    /// let (_meta, content) = grids[0].decode_with_hints(&hints)?;
+    /// assert_eq!(content, "https://github.com/WanzenBug/rqrr");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn decode_with_hints(&self, hints: &DecodeHints) -> DeQRResult<(MetaData, String)> {
+        let mut text = charset::TextDecoder::new();
+        let meta = decode::decode_with_hints_into(&self.grid, hints, &mut text)?;
+        Ok((self.oriented(meta), text.finish()?.text))
+    }
+
+    /// Try to decode the grid as text.
+    ///
+    /// Works like [`decode`](Self::decode), but also reports which character
+    /// set was chosen for data without ECI designator.
+    pub fn decode_text(&self) -> DeQRResult<(MetaData, DecodedText)> {
+        let mut text = charset::TextDecoder::new();
+        let meta = decode::decode_into(&self.grid, &mut text)?;
+        Ok((self.oriented(meta), text.finish()?))
+    }
+
+    /// Try to decode the grid into its segments.
+    ///
+    /// Instead of joining the content, returns every segment of the data
+    /// stream with its mode, character count, position in the stream and
+    /// decoded bytes.
+    pub fn decode_segments(&self) -> DeQRResult<(MetaData, Vec<Segment>)> {
+        let mut collector = decode::SegmentCollector::new();
+        let meta = decode::decode_into(&self.grid, &mut collector)?;
+        Ok((self.oriented(meta), collector.segments))
+    }
+
+    /// Try to decode the grid into its segments, keeping what was decoded
+    /// before an error in the data stream.
+    ///
+    /// Works like [`decode_segments`](Self::decode_segments), but errors in
+    /// the data stream, such as [`DeQRError::DataUnderflow`] or
+    /// [`DeQRError::UnknownDataType`], are returned along with all segments
+    /// that were decoded completely before them. Only a grid whose data can
+    /// not be read or corrected fails as a whole.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), Box<dyn ::std::error::Error>> {
+    /// let grid = rqrr::Grid::new(rqrr::SimpleGrid::encode(b"rqrr", 0)?);
+    /// let partial = grid.decode_partial()?;
+    /// assert_eq!(partial.error, None);
+    /// for segment in &partial.segments {
+    ///     println!("{:?}: {:?}", segment.mode, segment.data);
+    /// }
+    /// if let Some(err) = partial.error {
+    ///     println!("Stopped at bit {}: {}", err.bit_offset, err.error);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn decode_partial(&self) -> DeQRResult<PartialDecode> {
+        let mut partial = decode::decode_partial(&self.grid)?;
+        partial.meta = self.oriented(partial.meta);
+        Ok(partial)
+    }
+
+    /// Check the grid for deviations from ISO/IEC 18004.
+    ///
+    /// Decoding is lenient and accepts symbols with a missing terminator,
+    /// wrong padding or values out of range. This lists all such deviations
+    /// in the data stream and the remainder bits, instead of accepting the
+    /// symbol or failing on the first one. An error in the data stream is
+    /// listed as [`Violation::Payload`] and ends the check. Only a grid whose
+    /// data can not be read or corrected fails as a whole.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), Box<dyn ::std::error::Error>> {
+    /// let grid = rqrr::Grid::new(rqrr::SimpleGrid::encode(b"rqrr", 0)?);
+    /// let (_meta, violations) = grid.validate()?;
+    /// assert!(violations.is_empty());
+    /// for violation in &violations {
+    ///     println!("Not conforming: {:?}", violation);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn validate(&self) -> DeQRResult<(MetaData, Vec<Violation>)> {
+        let (meta, violations) = decode::validate(&self.grid)?;
+        Ok((self.oriented(meta), violations))
+    }
+
+    /// Locate the codewords that had to be corrected while decoding the grid.
+    ///
+    /// Each corrected codeword lists the modules it was read from. For grids
+    /// found in an image, [`RefGridImage::module_position`] maps them back to
+    /// the image, e.g. to draw where the code is damaged.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # #[cfg(not(feature = "img"))]
+    /// # fn main() {}
+    /// # #[cfg(feature = "img")]
+    /// # fn main() -> Result<(), Box<dyn ::std::error::Error>> {
+    /// # let img = image::open("tests/data/github.gif")?.to_luma8();
+    /// let mut img = rqrr::PreparedImage::prepare(img);
+    /// let grids = img.detect_grids();
+    /// let (meta, corrected) = grids[0].error_locations()?;
+    /// for codeword in &corrected {
+    ///     for &(y, x) in &codeword.modules {
+    ///         let pixel = grids[0].grid.module_position(y, x);
+    ///         println!("Codeword {} damaged at {:?}", codeword.index, pixel);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn error_locations(&self) -> DeQRResult<(MetaData, Vec<CorrectedCodeword>)> {
+        let (meta, corrected) = decode::error_locations(&self.grid)?;
+        Ok((self.oriented(meta), corrected))
     }
 
     /// Try to read metadata, and return the raw, uncorrected bit stream.
@@ -99,21 +299,69 @@ where
     /// If successful, returns the metadata along with the raw bit pattern.
     /// The raw data is still masked, so bits appear as in the source image.
     pub fn get_raw_data(&self) -> DeQRResult<(MetaData, RawData)> {
-        decode::get_raw(&self.grid, false)
+        let (meta, raw) = decode::get_raw(&self.grid, false)?;
+        Ok((self.oriented(meta), raw))
     }
 
     /// Try to decode the grid.
     ///
     /// Instead of returning a String, this method writes the decoded result to
-    /// the given writer
+    /// the given writer. The data is written as raw bytes, without applying
+    /// any ECI designator. Kanji segments are written as Shift JIS.
     ///
     /// **Warning**: This may lead to half decoded content to be written to the
     /// writer.
     pub fn decode_to<W>(&self, writer: W) -> DeQRResult<MetaData>
     where
-        W: Write,
+        W: ByteWrite,
     {
-        decode::decode(&self.grid, writer)
+        decode::decode(&self.grid, writer).map(|meta| self.oriented(meta))
+    }
+
+    /// Return the size of the scratch buffer needed to decode the grid with
+    /// [`decode_with_scratch`](Self::decode_with_scratch).
+    ///
+    /// The size covers every kind of symbol of the grid's size. It is 0 if
+    /// no symbol has that size. No buffer needs more than
+    /// [`MAX_SCRATCH_SIZE`] bytes.
+    pub fn scratch_size(&self) -> usize {
+        decode::scratch_size(self.grid.width(), self.grid.height())
+    }
+
+    /// Try to decode the grid without allocating.
+    ///
+    /// Works like [`decode_to`](Self::decode_to), but the data is read and
+    /// corrected in the given scratch buffer instead of on the stack or
+    /// heap. Fails with [`DeQRError::BufferTooSmall`] if the buffer is
+    /// smaller than [`scratch_size`](Self::scratch_size).
+    ///
+    /// As they would need allocation, the [`MetaData::ecc_blocks`] and
+    /// [`MetaData::eci`] of the returned metadata are left empty.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), Box<dyn ::std::error::Error>> {
+    /// let grid = rqrr::Grid::new(rqrr::SimpleGrid::encode(b"rqrr", 0)?);
+    /// let mut scratch = [0; rqrr::MAX_SCRATCH_SIZE];
+    /// let mut content = [0; 16];
+    /// let meta = grid.decode_with_scratch(&mut scratch, &mut content[..])?;
+    /// assert_eq!(meta.version, rqrr::Version(1));
+    /// assert_eq!(&content[..4], b"rqrr");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn decode_with_scratch<W>(&self, scratch: &mut [u8], writer: W) -> DeQRResult<MetaData>
+    where
+        W: ByteWrite,
+    {
+        decode::decode_with_scratch(&self.grid, scratch, writer).map(|meta| self.oriented(meta))
+    }
+
+    /// Report codes that were mirrored before the grid was read
+    fn oriented(&self, mut meta: MetaData) -> MetaData {
+        meta.mirrored ^= self.mirrored;
+        meta
     }
 }
 
@@ -128,7 +376,7 @@ pub trait BitGrid {
     /// Return the size of the grid.
     ///
     /// Since QR codes are always squares, the grid is assumed to be size *
-    /// size.
+    /// size. For rectangular grids, this is the width.
     fn size(&self) -> usize;
 
     /// Return the value of the bit at the given location.
@@ -136,19 +384,52 @@ pub trait BitGrid {
     /// `true` means 'black', `false` means 'white'
     fn bit(&self, y: usize, x: usize) -> bool;
 
+    /// Return whether the bit at the given location could not be read reliably.
+    ///
+    /// Codewords containing such bits are treated as erasures during error
+    /// correction, which allows correcting about twice as many of them. By
+    /// default, all bits are reliable.
+    fn uncertain(&self, _y: usize, _x: usize) -> bool {
+        false
+    }
+
+    /// Sample the grid again at a different size.
+    ///
+    /// Grids read from an image may have been measured at the wrong size.
+    /// Given the right size, e.g. from [`DecodeHints`], they can be re-sampled
+    /// with their corners kept in place. By default, grids can not be
+    /// re-sampled and `None` is returned.
+    fn resample(&self, _size: usize) -> Option<Box<dyn BitGrid + '_>> {
+        None
+    }
+
+    /// Return the width of the grid.
+    ///
+    /// Only rectangular Micro QR codes (rMQR) have a width different from
+    /// their height. By default, this is the size of the square grid.
+    fn width(&self) -> usize {
+        self.size()
+    }
+
+    /// Return the height of the grid.
+    ///
+    /// By default, this is the size of the square grid.
+    fn height(&self) -> usize {
+        self.size()
+    }
+
+    /// Save the grid as an image with one pixel per module.
+    ///
+    /// Panics if the image can not be written.
     #[cfg(feature = "img")]
+    #[deprecated(note = "use `Render::to_image` instead")]
     fn write_grid_to(&self, p: &str) {
-        let mut dyn_img = image::GrayImage::new(self.size() as u32, self.size() as u32);
-        for y in 0..self.size() {
-            for x in 0..self.size() {
-                let color = match self.bit(y, x) {
-                    true => 0,
-                    false => 255,
-                };
-                dyn_img.get_pixel_mut(x as u32, y as u32).0[0] = color;
-            }
-        }
-        dyn_img.save(p).unwrap();
+        let options = RenderOptions {
+            quiet_zone: 0,
+            module_size: 1,
+            invert: false,
+        };
+        render::image(self, &options).save(p).unwrap();
     }
 }
 
@@ -167,6 +448,18 @@ impl BitGrid for MirroredGrid<'_> {
     fn bit(&self, y: usize, x: usize) -> bool {
         self.0.bit(x, y)
     }
+
+    fn uncertain(&self, y: usize, x: usize) -> bool {
+        self.0.uncertain(x, y)
+    }
+
+    fn width(&self) -> usize {
+        self.0.height()
+    }
+
+    fn height(&self) -> usize {
+        self.0.width()
+    }
 }
 
 /// A basic GridImage that can be generated from a given function.
@@ -251,18 +544,54 @@ impl BitGrid for MirroredGrid<'_> {
 #[derive(Debug, Clone)]
 pub struct SimpleGrid {
     cell_bitmap: Vec<u8>,
-    size: usize,
+    width: usize,
+    height: usize,
 }
 
 impl SimpleGrid {
+    /// Encode data as the smallest QR code with the given error correction
+    /// level
+    ///
+    /// The error correction level uses the same values as
+    /// [`MetaData::ecc_level`]: 0 for M, 1 for L, 2 for H and 3 for Q. The
+    /// data is split into numeric, alphanumeric and byte segments to keep the
+    /// code small, and the mask with the lowest penalty score is chosen.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let simple = rqrr::SimpleGrid::encode(b"https://github.com/WanzenBug/rqrr", 0)?;
+    /// let grid = rqrr::Grid::new(simple);
+    /// let (meta, content) = grid.decode()?;
+    /// assert_eq!(meta.version, rqrr::Version(3));
+    /// assert_eq!(content, "https://github.com/WanzenBug/rqrr");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn encode(data: &[u8], ecc_level: u16) -> Result<Self, EncodeError> {
+        encode::encode(data, ecc_level)
+    }
+
     pub fn from_func<F>(size: usize, fill_func: F) -> Self
     where
         F: Fn(usize, usize) -> bool,
     {
-        let mut cell_bitmap = vec![0; (size * size).div_ceil(8)];
+        Self::from_func_rect(size, size, fill_func)
+    }
+
+    /// Generate a rectangular grid, as used by rMQR codes
+    ///
+    /// Like [`from_func`](Self::from_func), the function is called with the
+    /// x and y coordinate of every cell.
+    pub fn from_func_rect<F>(width: usize, height: usize, fill_func: F) -> Self
+    where
+        F: Fn(usize, usize) -> bool,
+    {
+        let mut cell_bitmap = vec![0; (width * height).div_ceil(8)];
         let mut c = 0;
-        for y in 0..size {
-            for x in 0..size {
+        for y in 0..height {
+            for x in 0..width {
                 if fill_func(x, y) {
                     cell_bitmap[c >> 3] |= 1 << (c & 7) as u8;
                 }
@@ -270,23 +599,39 @@ impl SimpleGrid {
             }
         }
 
-        SimpleGrid { cell_bitmap, size }
+        SimpleGrid {
+            cell_bitmap,
+            width,
+            height,
+        }
     }
 }
 
 impl BitGrid for SimpleGrid {
     fn size(&self) -> usize {
-        self.size
+        self.width
     }
 
     fn bit(&self, y: usize, x: usize) -> bool {
-        let c = y * self.size + x;
+        let c = y * self.width + x;
         self.cell_bitmap[c >> 3] & (1 << (c & 7) as u8) != 0
     }
+
+    fn width(&self) -> usize {
+        self.width
+    }
+
+    fn height(&self) -> usize {
+        self.height
+    }
 }
 
 /// Possible errors that can happen during decoding
+///
+/// New kinds of errors may be added in minor releases, so matches need a
+/// wildcard arm.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum DeQRError {
     /// Could not write the output to the output stream/string
     IoError,
@@ -306,20 +651,24 @@ pub enum DeQRError {
     InvalidGridSize,
     /// Output was not encoded in expected UTF8
     EncodingError,
+    /// Symbols do not form a consistent Structured Append sequence
+    StructuredAppend,
+    /// Scratch buffer is too small for the symbol
+    BufferTooSmall,
 }
 
 type DeQRResult<T> = Result<T, DeQRError>;
 
 impl Error for DeQRError {}
 
-impl From<::std::string::FromUtf8Error> for DeQRError {
-    fn from(_: ::std::string::FromUtf8Error) -> Self {
+impl From<FromUtf8Error> for DeQRError {
+    fn from(_: FromUtf8Error) -> Self {
         DeQRError::EncodingError
     }
 }
 
-impl ::std::fmt::Display for DeQRError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl ::core::fmt::Display for DeQRError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         let msg = match self {
             DeQRError::IoError => "IoError(Could not write to output)",
             DeQRError::DataUnderflow => "DataUnderflow(Expected more bits to decode)",
@@ -330,11 +679,81 @@ impl ::std::fmt::Display for DeQRError {
             DeQRError::InvalidVersion => "InvalidVersion(Invalid version or corrupt)",
             DeQRError::InvalidGridSize => "InvalidGridSize(Invalid version or corrupt)",
             DeQRError::EncodingError => "Encoding(Not UTF8)",
+            DeQRError::StructuredAppend => {
+                "StructuredAppend(Symbol not part of sequence or parity mismatch)"
+            }
+            DeQRError::BufferTooSmall => "BufferTooSmall(Scratch buffer too small for symbol)",
         };
         write!(f, "{msg}")
     }
 }
 
+/// Output for the raw bytes written by [`Grid::decode_to`]
+///
+/// Bytes can be collected in a `Vec<u8>` or written to a `&mut [u8]`, which
+/// advances past the written bytes like `std::io::Write` does. With the `std`
+/// feature, any `std::io::Write` can be used through `IoWriter`. Custom
+/// types, such as UART drivers, can implement it as well.
+pub trait ByteWrite {
+    /// Write all bytes, or fail with [`DeQRError::IoError`]
+    fn write_all(&mut self, data: &[u8]) -> DeQRResult<()>;
+}
+
+impl ByteWrite for Vec<u8> {
+    fn write_all(&mut self, data: &[u8]) -> DeQRResult<()> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+impl ByteWrite for &mut [u8] {
+    fn write_all(&mut self, data: &[u8]) -> DeQRResult<()> {
+        if data.len() > self.len() {
+            return Err(DeQRError::IoError);
+        }
+        let (head, tail) = core::mem::take(self).split_at_mut(data.len());
+        head.copy_from_slice(data);
+        *self = tail;
+        Ok(())
+    }
+}
+
+impl<W> ByteWrite for &mut W
+where
+    W: ByteWrite + ?Sized,
+{
+    fn write_all(&mut self, data: &[u8]) -> DeQRResult<()> {
+        (**self).write_all(data)
+    }
+}
+
+/// Adapter to write the output of [`Grid::decode_to`] to a `std::io::Write`
+///
+/// # Example
+///
+/// ```rust
+/// # fn main() -> Result<(), Box<dyn ::std::error::Error>> {
+/// let grid = rqrr::Grid::new(rqrr::SimpleGrid::encode(b"rqrr", 0)?);
+/// let mut content = std::io::Cursor::new(Vec::new());
+/// grid.decode_to(rqrr::IoWriter(&mut content))?;
+/// assert_eq!(content.into_inner(), b"rqrr");
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default)]
+pub struct IoWriter<W>(pub W);
+
+#[cfg(feature = "std")]
+impl<W> ByteWrite for IoWriter<W>
+where
+    W: std::io::Write,
+{
+    fn write_all(&mut self, data: &[u8]) -> DeQRResult<()> {
+        self.0.write_all(data).map_err(|_| DeQRError::IoError)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -702,4 +1121,18 @@ mod tests {
 
         assert_eq!(b"1234567891011121314151617181920".as_ref(), &vec[..])
     }
+
+    #[test]
+    fn test_decode_to_byte_write() {
+        let grid = Grid::new(SimpleGrid::encode(b"rqrr", 0).unwrap());
+
+        let mut buf = [0; 8];
+        let meta = grid.decode_to(&mut buf[..]).unwrap();
+        assert_eq!(meta.version, Version(1));
+        assert_eq!(&buf, b"rqrr\0\0\0\0");
+
+        let mut buf = [0; 3];
+        let result = grid.decode_to(&mut buf[..]);
+        assert_eq!(result.unwrap_err(), DeQRError::IoError);
+    }
 }
@@ -0,0 +1,751 @@
+//! Structured content of decoded codes
+//!
+//! Besides plain text and links, codes often hold content in one of a few
+//! well known formats: network credentials, contacts, messages to write,
+//! numbers to call, locations and one-time password secrets. These formats
+//! are recognized by their prefix, and split into their fields. Content in no
+//! known format, or malformed content, is kept as plain text.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Content of a code, classified by its format
+#[derive(Debug, Clone, PartialEq)]
+pub enum Payload {
+    /// Wi-Fi network credentials, `WIFI:S:<ssid>;T:<auth>;P:<password>;;`
+    Wifi(Wifi),
+    /// Contact information, as `MECARD:` or vCard `BEGIN:VCARD`
+    Contact(Contact),
+    /// Email to write, `mailto:<address>?subject=<subject>&body=<body>`
+    Email(Email),
+    /// Text message to send, `SMSTO:<number>:<message>`
+    Sms(Sms),
+    /// Phone number to call, `tel:<number>`
+    Phone(String),
+    /// Location, `geo:<latitude>,<longitude>`
+    Geo(Geo),
+    /// Secret for one-time passwords, `otpauth://totp/<label>?secret=<secret>`
+    Otp(Otp),
+    /// Any other content
+    Text(String),
+}
+
+impl Payload {
+    /// Classify decoded content by its format
+    ///
+    /// Prefixes are matched ignoring case and surrounding whitespace. Content
+    /// that does not start with a known prefix, or that is malformed, is
+    /// returned as [`Payload::Text`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # fn main() -> Result<(), Box<dyn ::std::error::Error>> {
+    /// let code = rqrr::SimpleGrid::encode(br"WIFI:T:WPA;S:Cafe\;Bar;P:secret;;", 0)?;
+    /// let (_meta, content) = rqrr::Grid::new(code).decode()?;
+    /// match rqrr::Payload::parse(&content) {
+    ///     rqrr::Payload::Wifi(wifi) => {
+    ///         assert_eq!(wifi.ssid, "Cafe;Bar");
+    ///         assert_eq!(wifi.password.as_deref(), Some("secret"));
+    ///     }
+    ///     other => panic!("Expected Wi-Fi credentials, got {:?}", other),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn parse(content: &str) -> Payload {
+        parse_structured(content.trim()).unwrap_or_else(|| Payload::Text(content.to_string()))
+    }
+}
+
+/// Wi-Fi network credentials
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Wifi {
+    /// Name of the network
+    pub ssid: String,
+    /// Authentication type, such as `WPA`, `WEP` or `nopass`
+    pub auth: Option<String>,
+    /// Password of the network
+    pub password: Option<String>,
+    /// Whether the network does not broadcast its name
+    pub hidden: bool,
+}
+
+/// Contact information
+///
+/// Postal addresses and organizations consisting of several components are
+/// joined with `", "`, leaving out empty components.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Contact {
+    /// Full name, given name first
+    pub name: Option<String>,
+    /// Organization, with its units
+    pub organization: Option<String>,
+    /// Phone numbers
+    pub phones: Vec<String>,
+    /// Email addresses
+    pub emails: Vec<String>,
+    /// Postal addresses
+    pub addresses: Vec<String>,
+    /// Websites
+    pub urls: Vec<String>,
+    /// Birthday, as given in the code
+    pub birthday: Option<String>,
+    /// Free form note
+    pub note: Option<String>,
+}
+
+/// Email to write
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Email {
+    /// Recipients
+    pub to: Vec<String>,
+    /// Subject of the email
+    pub subject: Option<String>,
+    /// Text of the email
+    pub body: Option<String>,
+}
+
+/// Text message to send
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Sms {
+    /// Phone number of the recipient
+    pub number: String,
+    /// Text of the message
+    pub message: Option<String>,
+}
+
+/// Location in WGS 84 coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Geo {
+    /// Latitude in degrees, between -90 and 90
+    pub latitude: f64,
+    /// Longitude in degrees, between -180 and 180
+    pub longitude: f64,
+    /// Altitude in meters
+    pub altitude: Option<f64>,
+    /// Search query for the location, from the `q` parameter
+    pub query: Option<String>,
+}
+
+/// Secret for one-time passwords, as used by authenticator apps
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Otp {
+    /// How passwords are generated
+    pub kind: OtpKind,
+    /// Provider of the account
+    pub issuer: Option<String>,
+    /// Name of the account, usually a user name or an email address
+    pub account: String,
+    /// The shared secret, base32 encoded
+    pub secret: String,
+    /// Hash algorithm, `SHA1` by default
+    pub algorithm: String,
+    /// Number of digits of a password, 6 by default
+    pub digits: u32,
+}
+
+/// How one-time passwords are generated
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum OtpKind {
+    /// Time based, with a new password every `period` seconds, 30 by default
+    Totp { period: u32 },
+    /// Counter based, starting at `counter`
+    Hotp { counter: u64 },
+}
+
+fn parse_structured(content: &str) -> Option<Payload> {
+    if let Some(rest) = strip_prefix_ignore_case(content, "WIFI:") {
+        parse_wifi(rest).map(Payload::Wifi)
+    } else if let Some(rest) = strip_prefix_ignore_case(content, "MECARD:") {
+        Some(Payload::Contact(parse_mecard(rest)))
+    } else if strip_prefix_ignore_case(content, "BEGIN:VCARD").is_some() {
+        Some(Payload::Contact(parse_vcard(content)))
+    } else if let Some(rest) = strip_prefix_ignore_case(content, "mailto:") {
+        parse_mailto(rest).map(Payload::Email)
+    } else if let Some(rest) = strip_prefix_ignore_case(content, "SMSTO:") {
+        let (number, message) = rest.split_once(':').unwrap_or((rest, ""));
+        Some(Payload::Sms(Sms {
+            number: number.to_string(),
+            message: non_empty(message.to_string()),
+        }))
+    } else if let Some(rest) = strip_prefix_ignore_case(content, "tel:") {
+        percent_decode(rest).and_then(non_empty).map(Payload::Phone)
+    } else if let Some(rest) = strip_prefix_ignore_case(content, "geo:") {
+        parse_geo(rest).map(Payload::Geo)
+    } else if let Some(rest) = strip_prefix_ignore_case(content, "otpauth://") {
+        parse_otpauth(rest).map(Payload::Otp)
+    } else {
+        None
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(data: &'a str, prefix: &str) -> Option<&'a str> {
+    match data.get(..prefix.len()) {
+        Some(start) if start.eq_ignore_ascii_case(prefix) => Some(&data[prefix.len()..]),
+        _ => None,
+    }
+}
+
+fn non_empty(value: String) -> Option<String> {
+    match value.is_empty() {
+        true => None,
+        false => Some(value),
+    }
+}
+
+/// Split at every separator that is not escaped by a backslash
+///
+/// The parts are returned as they are, still escaped.
+fn split_unescaped(data: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in data.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == separator {
+            parts.push(&data[start..i]);
+            start = i + 1;
+        }
+    }
+    parts.push(&data[start..]);
+    parts
+}
+
+/// Remove backslash escapes, as used by `WIFI:`, `MECARD:` and vCards
+///
+/// With `newlines`, the vCard escapes `\n` and `\N` stand for a line break.
+fn unescape(data: &str, newlines: bool) -> String {
+    let mut out = String::with_capacity(data.len());
+    let mut chars = data.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') if newlines => out.push('\n'),
+            Some(escaped) => out.push(escaped),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Split `KEY:value` fields separated by `;`, as used by `WIFI:` and
+/// `MECARD:`
+///
+/// Keys are returned in upper case, values still escaped. Empty fields, like
+/// the final `;;`, are skipped.
+fn key_value_fields(data: &str) -> impl Iterator<Item = (String, &str)> {
+    split_unescaped(data, ';').into_iter().filter_map(|field| {
+        let (key, value) = field.split_once(':')?;
+        Some((key.trim().to_ascii_uppercase(), value))
+    })
+}
+
+fn parse_wifi(data: &str) -> Option<Wifi> {
+    let mut ssid = None;
+    let mut wifi = Wifi {
+        ssid: String::new(),
+        auth: None,
+        password: None,
+        hidden: false,
+    };
+    for (key, value) in key_value_fields(data) {
+        let value = unescape(value, false);
+        match key.as_str() {
+            "S" => ssid = Some(value),
+            "T" => wifi.auth = non_empty(value),
+            "P" => wifi.password = non_empty(value),
+            "H" => wifi.hidden = value.eq_ignore_ascii_case("true"),
+            _ => (),
+        }
+    }
+    wifi.ssid = ssid?;
+    Some(wifi)
+}
+
+/// Join the non-empty components of a structured value
+fn join_components<'a, I>(components: I, separator: &str) -> Option<String>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut joined = String::new();
+    for component in components {
+        let component = component.trim();
+        if component.is_empty() {
+            continue;
+        }
+        if !joined.is_empty() {
+            joined.push_str(separator);
+        }
+        joined.push_str(component);
+    }
+    non_empty(joined)
+}
+
+fn parse_mecard(data: &str) -> Contact {
+    let mut contact = Contact::default();
+    for (key, value) in key_value_fields(data) {
+        // Names and addresses consist of components separated by commas
+        let components = || {
+            split_unescaped(value, ',')
+                .into_iter()
+                .map(|component| unescape(component, false))
+                .collect::<Vec<_>>()
+        };
+        match key.as_str() {
+            "N" => {
+                // Family name first, followed by the given name
+                let names = components();
+                contact.name = join_components(names.iter().rev().map(String::as_str), " ");
+            }
+            "ADR" => {
+                let parts = components();
+                contact
+                    .addresses
+                    .extend(join_components(parts.iter().map(String::as_str), ", "));
+            }
+            "TEL" => contact.phones.extend(non_empty(unescape(value, false))),
+            "EMAIL" => contact.emails.extend(non_empty(unescape(value, false))),
+            "URL" => contact.urls.extend(non_empty(unescape(value, false))),
+            "ORG" => contact.organization = non_empty(unescape(value, false)),
+            "BDAY" => contact.birthday = non_empty(unescape(value, false)),
+            "NOTE" => contact.note = non_empty(unescape(value, false)),
+            _ => (),
+        }
+    }
+    contact
+}
+
+fn parse_vcard(data: &str) -> Contact {
+    // Lines starting with whitespace continue the previous line
+    let mut lines: Vec<String> = Vec::new();
+    for line in data.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continued), Some(last)) => last.push_str(continued),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut contact = Contact::default();
+    let mut structured_name = None;
+    for line in &lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Drop parameters and group prefixes, as in `item1.TEL;TYPE=CELL`
+        let name = name.split(';').next().unwrap_or_default();
+        let name = name.rsplit('.').next().unwrap_or_default();
+        let components = || {
+            split_unescaped(value, ';')
+                .into_iter()
+                .map(|component| unescape(component, true))
+                .collect::<Vec<_>>()
+        };
+
+        match name.to_ascii_uppercase().as_str() {
+            "FN" => contact.name = non_empty(unescape(value, true)),
+            "N" => {
+                // Family, given, additional names, prefixes and suffixes
+                let names = components();
+                let order = [3, 1, 2, 0, 4];
+                structured_name = join_components(
+                    order
+                        .iter()
+                        .filter_map(|&i| names.get(i).map(String::as_str)),
+                    " ",
+                );
+            }
+            "ORG" => {
+                let units = components();
+                contact.organization = join_components(units.iter().map(String::as_str), ", ");
+            }
+            "ADR" => {
+                let parts = components();
+                contact
+                    .addresses
+                    .extend(join_components(parts.iter().map(String::as_str), ", "));
+            }
+            "TEL" => {
+                let number = unescape(value, true);
+                let number = strip_prefix_ignore_case(&number, "tel:").unwrap_or(&number);
+                contact.phones.extend(non_empty(number.to_string()));
+            }
+            "EMAIL" => contact.emails.extend(non_empty(unescape(value, true))),
+            "URL" => contact.urls.extend(non_empty(unescape(value, true))),
+            "BDAY" => contact.birthday = non_empty(unescape(value, true)),
+            "NOTE" => contact.note = non_empty(unescape(value, true)),
+            "END" => break,
+            _ => (),
+        }
+    }
+    if contact.name.is_none() {
+        contact.name = structured_name;
+    }
+    contact
+}
+
+/// Decode `%XX` escapes of URIs
+///
+/// Fails on incomplete escapes and if the result is not valid UTF-8.
+fn percent_decode(data: &str) -> Option<String> {
+    let bytes = data.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3)?;
+            if !hex.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            let hex = core::str::from_utf8(hex).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Split a URI into its path and its decoded query parameters
+///
+/// Parameter names are returned in lower case.
+fn split_query(data: &str) -> Option<(&str, Vec<(String, String)>)> {
+    let (path, query) = data.split_once('?').unwrap_or((data, ""));
+    let mut params = Vec::new();
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let (key, value) = param.split_once('=').unwrap_or((param, ""));
+        params.push((
+            percent_decode(key)?.to_ascii_lowercase(),
+            percent_decode(value)?,
+        ));
+    }
+    Some((path, params))
+}
+
+fn parse_mailto(data: &str) -> Option<Email> {
+    let (addresses, params) = split_query(data)?;
+    let mut email = Email {
+        to: Vec::new(),
+        subject: None,
+        body: None,
+    };
+    for address in addresses.split(',').filter(|address| !address.is_empty()) {
+        email.to.push(percent_decode(address)?);
+    }
+    for (key, value) in params {
+        match key.as_str() {
+            "to" => email.to.extend(value.split(',').map(ToString::to_string)),
+            "subject" => email.subject = non_empty(value),
+            "body" => email.body = non_empty(value),
+            _ => (),
+        }
+    }
+    Some(email)
+}
+
+fn parse_geo(data: &str) -> Option<Geo> {
+    let (path, params) = split_query(data)?;
+    // Parameters such as the coordinate system follow after `;`
+    let coordinates = path.split(';').next().unwrap_or_default();
+    let mut values = coordinates
+        .split(',')
+        .map(|value| value.trim().parse::<f64>());
+    let latitude = values.next()?.ok()?;
+    let longitude = values.next()?.ok()?;
+    let altitude = values.next().transpose().ok()?;
+    if values.next().is_some()
+        || !(-90.0..=90.0).contains(&latitude)
+        || !(-180.0..=180.0).contains(&longitude)
+        || !altitude.is_none_or(f64::is_finite)
+    {
+        return None;
+    }
+
+    let query = params
+        .into_iter()
+        .find(|(key, _)| key == "q")
+        .and_then(|(_, value)| non_empty(value));
+    Some(Geo {
+        latitude,
+        longitude,
+        altitude,
+        query,
+    })
+}
+
+fn parse_otpauth(data: &str) -> Option<Otp> {
+    let (path, params) = split_query(data)?;
+    let (kind, label) = path.split_once('/')?;
+    let label = percent_decode(label)?;
+    let (mut issuer, account) = match label.split_once(':') {
+        Some((issuer, account)) => (non_empty(issuer.to_string()), account.trim_start()),
+        None => (None, label.as_str()),
+    };
+
+    let mut secret = None;
+    let mut algorithm = "SHA1".to_string();
+    let mut digits = 6;
+    let mut period = 30;
+    let mut counter = None;
+    for (key, value) in params {
+        match key.as_str() {
+            "secret" => secret = Some(value),
+            "issuer" => issuer = non_empty(value).or(issuer),
+            "algorithm" => algorithm = value.to_ascii_uppercase(),
+            "digits" => digits = value.parse().ok()?,
+            "period" => period = value.parse().ok()?,
+            "counter" => counter = Some(value.parse().ok()?),
+            _ => (),
+        }
+    }
+
+    // The secret is base32 encoded, possibly with padding
+    let secret = secret?;
+    let base32 = |c: char| matches!(c.to_ascii_uppercase(), 'A'..='Z' | '2'..='7' | '=');
+    if secret.is_empty() || !secret.chars().all(base32) {
+        return None;
+    }
+
+    let kind = match kind.to_ascii_lowercase().as_str() {
+        "totp" => OtpKind::Totp { period },
+        "hotp" => OtpKind::Hotp { counter: counter? },
+        _ => return None,
+    };
+    Some(Otp {
+        kind,
+        issuer,
+        account: account.to_string(),
+        secret,
+        algorithm,
+        digits,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn test_wifi() {
+        assert_eq!(
+            Payload::parse(r#"WIFI:T:WPA;S:My\;Net\\work;P:pa\:ss\,word;H:true;;"#),
+            Payload::Wifi(Wifi {
+                ssid: r"My;Net\work".to_string(),
+                auth: Some("WPA".to_string()),
+                password: Some("pa:ss,word".to_string()),
+                hidden: true,
+            })
+        );
+        assert_eq!(
+            Payload::parse("wifi:s:Open;t:nopass;p:;;\n"),
+            Payload::Wifi(Wifi {
+                ssid: "Open".to_string(),
+                auth: Some("nopass".to_string()),
+                password: None,
+                hidden: false,
+            })
+        );
+        // Without a network name, the content is kept as text
+        assert_eq!(
+            Payload::parse("WIFI:T:WPA;P:secret;;"),
+            Payload::Text("WIFI:T:WPA;P:secret;;".to_string())
+        );
+    }
+
+    #[test]
+    fn test_mecard() {
+        let payload = Payload::parse(
+            r"MECARD:N:Doe,John;TEL:+1555123;TEL:+1555456;EMAIL:john@example.com;ADR:,,1 Main St\; Apt 2,Springfield,,12345,US;ORG:ACME;NOTE:Hello\: world;;",
+        );
+        assert_eq!(
+            payload,
+            Payload::Contact(Contact {
+                name: Some("John Doe".to_string()),
+                organization: Some("ACME".to_string()),
+                phones: strings(&["+1555123", "+1555456"]),
+                emails: strings(&["john@example.com"]),
+                addresses: strings(&["1 Main St; Apt 2, Springfield, 12345, US"]),
+                note: Some("Hello: world".to_string()),
+                ..Contact::default()
+            })
+        );
+    }
+
+    #[test]
+    fn test_vcard() {
+        let vcard = "BEGIN:VCARD\r\n\
+            VERSION:3.0\r\n\
+            N:Doe;John;;Dr.;\r\n\
+            ORG:ACME;Research\r\n\
+            item1.TEL;TYPE=CELL:+1 555 123\r\n\
+            TEL;VALUE=uri:tel:+1-555-456\r\n\
+            EMAIL;TYPE=INTERNET:john@exam\r\n ple.com\r\n\
+            ADR;TYPE=WORK:;;1 Main St\\, Apt 2;Springfield;;12345;US\r\n\
+            NOTE:First line\\nSecond line\r\n\
+            BDAY:1970-01-01\r\n\
+            END:VCARD\r\n\
+            NOTE:Ignored\r\n";
+        assert_eq!(
+            Payload::parse(vcard),
+            Payload::Contact(Contact {
+                name: Some("Dr. John Doe".to_string()),
+                organization: Some("ACME, Research".to_string()),
+                phones: strings(&["+1 555 123", "+1-555-456"]),
+                emails: strings(&["john@example.com"]),
+                addresses: strings(&["1 Main St, Apt 2, Springfield, 12345, US"]),
+                urls: vec![],
+                birthday: Some("1970-01-01".to_string()),
+                note: Some("First line\nSecond line".to_string()),
+            })
+        );
+
+        // The formatted name takes precedence over the structured one
+        let payload = Payload::parse("BEGIN:VCARD\nN:Doe;John\nFN:Johnny\nEND:VCARD");
+        let Payload::Contact(contact) = payload else {
+            panic!("Expected a contact, got {:?}", payload);
+        };
+        assert_eq!(contact.name.as_deref(), Some("Johnny"));
+    }
+
+    #[test]
+    fn test_mailto_sms_tel() {
+        assert_eq!(
+            Payload::parse("mailto:a@example.com,b%40example.com?Subject=Hi%20there&body=Line%0Abreak&cc=c@example.com"),
+            Payload::Email(Email {
+                to: strings(&["a@example.com", "b@example.com"]),
+                subject: Some("Hi there".to_string()),
+                body: Some("Line\nbreak".to_string()),
+            })
+        );
+        assert_eq!(
+            Payload::parse("mailto:?to=a@example.com"),
+            Payload::Email(Email {
+                to: strings(&["a@example.com"]),
+                subject: None,
+                body: None,
+            })
+        );
+        // Incomplete escapes are malformed
+        assert_eq!(
+            Payload::parse("mailto:a@example.com?subject=100%"),
+            Payload::Text("mailto:a@example.com?subject=100%".to_string())
+        );
+
+        assert_eq!(
+            Payload::parse("SMSTO:+1555123:Meet at 10:30"),
+            Payload::Sms(Sms {
+                number: "+1555123".to_string(),
+                message: Some("Meet at 10:30".to_string()),
+            })
+        );
+        assert_eq!(
+            Payload::parse("smsto:+1555123"),
+            Payload::Sms(Sms {
+                number: "+1555123".to_string(),
+                message: None,
+            })
+        );
+
+        assert_eq!(
+            Payload::parse("TEL:+1-555-123"),
+            Payload::Phone("+1-555-123".to_string())
+        );
+        assert_eq!(Payload::parse("tel:"), Payload::Text("tel:".to_string()));
+    }
+
+    #[test]
+    fn test_geo() {
+        assert_eq!(
+            Payload::parse("geo:48.2010,16.3695,183;crs=wgs84?q=Vienna%20Opera"),
+            Payload::Geo(Geo {
+                latitude: 48.2010,
+                longitude: 16.3695,
+                altitude: Some(183.0),
+                query: Some("Vienna Opera".to_string()),
+            })
+        );
+        assert_eq!(
+            Payload::parse("geo:-33.8568,151.2153"),
+            Payload::Geo(Geo {
+                latitude: -33.8568,
+                longitude: 151.2153,
+                altitude: None,
+                query: None,
+            })
+        );
+        for invalid in [
+            "geo:91,0",
+            "geo:0,181",
+            "geo:0",
+            "geo:a,b",
+            "geo:0,0,0,0",
+            "geo:0,0,inf",
+        ] {
+            assert_eq!(Payload::parse(invalid), Payload::Text(invalid.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_otpauth() {
+        assert_eq!(
+            Payload::parse(
+                "otpauth://totp/Example:alice%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=Example"
+            ),
+            Payload::Otp(Otp {
+                kind: OtpKind::Totp { period: 30 },
+                issuer: Some("Example".to_string()),
+                account: "alice@example.com".to_string(),
+                secret: "JBSWY3DPEHPK3PXP".to_string(),
+                algorithm: "SHA1".to_string(),
+                digits: 6,
+            })
+        );
+        assert_eq!(
+            Payload::parse(
+                "otpauth://hotp/bob?secret=jbswy3dp&counter=7&digits=8&algorithm=sha256"
+            ),
+            Payload::Otp(Otp {
+                kind: OtpKind::Hotp { counter: 7 },
+                issuer: None,
+                account: "bob".to_string(),
+                secret: "jbswy3dp".to_string(),
+                algorithm: "SHA256".to_string(),
+                digits: 8,
+            })
+        );
+        for invalid in [
+            "otpauth://totp/bob",
+            "otpauth://totp/bob?secret=not-base32",
+            "otpauth://hotp/bob?secret=JBSWY3DP",
+            "otpauth://totp/bob?secret=JBSWY3DP&digits=six",
+            "otpauth://motp/bob?secret=JBSWY3DP",
+        ] {
+            assert_eq!(Payload::parse(invalid), Payload::Text(invalid.to_string()));
+        }
+    }
+
+    #[test]
+    fn test_text() {
+        for text in [
+            "https://github.com/WanzenBug/rqrr",
+            "Hello, World!",
+            "",
+            "WIFI",
+        ] {
+            assert_eq!(Payload::parse(text), Payload::Text(text.to_string()));
+        }
+    }
+}
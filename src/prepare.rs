@@ -1,4 +1,7 @@
-use std::{cmp, num::NonZeroUsize};
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::{cmp, num::NonZeroUsize};
 
 use crate::identify::match_capstones::CapStoneGroup;
 use crate::identify::Point;
@@ -12,6 +15,13 @@ use lru::LruCache;
 pub struct PreparedImage<S> {
     buffer: S,
     cache: LruCache<u8, ColoredRegion>,
+    /// Pixels close to the binarization threshold, one bit per pixel
+    ///
+    /// Empty if the image was already binarized.
+    uncertain_bitmap: Vec<u8>,
+    /// Whether light and dark are currently swapped, to search for
+    /// light-on-dark codes
+    inverted: bool,
 }
 
 impl<S> Clone for PreparedImage<S>
@@ -27,6 +37,8 @@ where
         PreparedImage {
             buffer: self.buffer.clone(),
             cache,
+            uncertain_bitmap: self.uncertain_bitmap.clone(),
+            inverted: self.inverted,
         }
     }
 }
@@ -179,7 +191,21 @@ impl<S> PreparedImage<S>
 where
     S: ImageBuffer,
 {
-    pub fn prepare(mut buf: S) -> Self {
+    pub fn prepare(buf: S) -> Self {
+        Self::binarize(buf, false)
+    }
+
+    /// Prepare an image like [`prepare`](Self::prepare), but remember which
+    /// pixels were close to the binarization threshold
+    ///
+    /// Codewords sampled from such pixels are treated as erasures when
+    /// decoding, which roughly doubles the damage that can be corrected. This
+    /// needs one more bit of memory per pixel.
+    pub fn prepare_with_erasures(buf: S) -> Self {
+        Self::binarize(buf, true)
+    }
+
+    fn binarize(mut buf: S, erasures: bool) -> Self {
         let w = buf.width();
         let h = buf.height();
         let mut row_average = vec![0; w];
@@ -187,6 +213,10 @@ where
         let mut avg_u = 0;
 
         let threshold_s = cmp::max(w / 8, 1);
+        let mut uncertain_bitmap = match erasures {
+            true => vec![0; (w * h).div_ceil(8)],
+            false => Vec::new(),
+        };
 
         for y in 0..h {
             row_average.fill(0);
@@ -205,26 +235,42 @@ where
 
             #[allow(clippy::needless_range_loop)]
             for x in 0..w {
-                let fill = if (buf.get_pixel(x, y) as usize)
-                    < row_average[x] * (100 - 5) / (200 * threshold_s)
-                {
+                let pixel = buf.get_pixel(x, y) as usize;
+                let threshold = row_average[x] * (100 - 5) / (200 * threshold_s);
+                let fill = if pixel < threshold {
                     PixelColor::Black
                 } else {
                     PixelColor::White
                 };
                 buf.set_pixel(x, y, fill.into());
+
+                /* Pixels within 1/8 of the threshold could have gone either way */
+                if erasures && pixel.abs_diff(threshold) * 8 < threshold {
+                    let c = y * w + x;
+                    uncertain_bitmap[c >> 3] |= 1 << (c & 7);
+                }
             }
         }
 
         PreparedImage {
             buffer: buf,
             cache: LruCache::new(NonZeroUsize::new(251).unwrap()),
+            uncertain_bitmap,
+            inverted: false,
         }
     }
 
     /// Group [CapStones](struct.CapStone.html) into [Grids](struct.Grid.html)
     /// that are likely QR codes
     ///
+    /// CapStones that are not part of any group are checked for the timing
+    /// patterns of a Micro QR code, or the format information and sub-finder
+    /// of a rectangular Micro QR code.
+    ///
+    /// Only codes with dark modules on a light background are found, see
+    /// [`detect_grids_inverted`](Self::detect_grids_inverted) for
+    /// light-on-dark codes.
+    ///
     /// Return a vector of Grids
     pub fn detect_grids<'a>(
         &'a mut self,
@@ -232,13 +278,38 @@ where
     where
         S: Clone,
     {
+        self.set_inverted(false);
+        self.detect_grids_as_is()
+    }
+
+    /// Find grids of codes with light modules on a dark background
+    ///
+    /// Works like [`detect_grids`](Self::detect_grids), with light and dark
+    /// swapped. The swap is done in place, so no copy of the image is made,
+    /// and undone by the next call to `detect_grids`. The grids found are
+    /// marked as [`inverted`](crate::Grid::is_inverted).
+    ///
+    /// Return a vector of Grids
+    pub fn detect_grids_inverted<'a>(
+        &'a mut self,
+    ) -> Vec<crate::Grid<crate::identify::grid::RefGridImage<'a, S>>>
+    where
+        S: Clone,
+    {
+        self.set_inverted(true);
+        self.detect_grids_as_is()
+    }
+
+    /// Find all grids, with light and dark as currently set
+    fn detect_grids_as_is<'a>(
+        &'a mut self,
+    ) -> Vec<crate::Grid<crate::identify::grid::RefGridImage<'a, S>>>
+    where
+        S: Clone,
+    {
+        let locations = self.find_grid_locations();
+        let this: &'a Self = self;
         let mut res = Vec::new();
-        let stones = crate::capstones_from_image(self);
-        let groups = self.find_groupings(stones);
-        let locations: Vec<_> = groups
-            .into_iter()
-            .filter_map(|v| crate::SkewedGridLocation::from_group(self, v))
-            .collect();
         for grid_location in locations {
             let bounds = [
                 grid_location.c.map(0.0, 0.0),
@@ -247,35 +318,92 @@ where
                     .map(grid_location.grid_size as f64 + 1.0, 0.0),
                 grid_location.c.map(
                     grid_location.grid_size as f64 + 1.0,
-                    grid_location.grid_size as f64 + 1.0,
+                    grid_location.grid_height as f64 + 1.0,
                 ),
                 grid_location
                     .c
-                    .map(0.0, grid_location.grid_size as f64 + 1.0),
+                    .map(0.0, grid_location.grid_height as f64 + 1.0),
             ];
-            let grid = grid_location.into_grid_image(self);
-            res.push(crate::Grid { grid, bounds });
+            let mirrored = grid_location.mirrored;
+            let grid = grid_location.into_grid_image(this);
+            res.push(crate::Grid {
+                grid,
+                bounds,
+                inverted: this.inverted,
+                mirrored,
+            });
         }
 
         res
     }
 
+    /// Find the locations of all grids with dark modules on a light
+    /// background
+    fn find_grid_locations(&mut self) -> Vec<crate::SkewedGridLocation>
+    where
+        S: Clone,
+    {
+        let stones = crate::capstones_from_image(self);
+        let mut used_capstones = Vec::new();
+        let groups = self.find_groupings(&stones, &mut used_capstones);
+        let mut locations: Vec<_> = groups
+            .into_iter()
+            .filter_map(|v| crate::SkewedGridLocation::from_group(self, v))
+            .collect();
+        // Capstones not part of a regular QR code may belong to a (rectangular)
+        // Micro QR code
+        locations.extend(
+            stones
+                .into_iter()
+                .enumerate()
+                .filter(|(idx, _)| !used_capstones.contains(idx))
+                .filter_map(|(_, stone)| crate::SkewedGridLocation::from_capstone(self, stone)),
+        );
+        locations
+    }
+
+    /// Swap light and dark, unless they already are as requested
+    ///
+    /// Light pixels turn dark, and all others, including the regions colored
+    /// by an earlier search, turn light.
+    fn set_inverted(&mut self, inverted: bool) {
+        if self.inverted == inverted {
+            return;
+        }
+        for y in 0..self.buffer.height() {
+            for x in 0..self.buffer.width() {
+                let color = match PixelColor::from(self.buffer.get_pixel(x, y)) {
+                    PixelColor::White => PixelColor::Black,
+                    _ => PixelColor::White,
+                };
+                self.buffer.set_pixel(x, y, color.into());
+            }
+        }
+        // Colored regions refer to the pixels before the swap
+        self.cache.clear();
+        self.inverted = inverted;
+    }
+
     /// Find CapStones that form a grid
     ///
     /// By trying to match up the relative perspective of 3
     /// [CapStones](struct.CapStone.html) along with other criteria we can find the
-    /// CapStones that corner the same QR code.
-    fn find_groupings(&mut self, capstones: Vec<crate::CapStone>) -> Vec<CapStoneGroup>
+    /// CapStones that corner the same QR code. The indexes of all grouped
+    /// CapStones are added to `used_capstones`.
+    fn find_groupings(
+        &mut self,
+        capstones: &[crate::CapStone],
+        used_capstones: &mut Vec<usize>,
+    ) -> Vec<CapStoneGroup>
     where
         S: Clone,
     {
-        let mut used_capstones = Vec::new();
         let mut groups = Vec::new();
         for idx in 0..capstones.len() {
             if used_capstones.contains(&idx) {
                 continue;
             }
-            let pairs = crate::identify::find_and_rank_possible_neighbors(&capstones, idx);
+            let pairs = crate::identify::find_and_rank_possible_neighbors(capstones, idx);
             for pair in pairs {
                 if used_capstones.contains(&pair.0) || used_capstones.contains(&pair.1) {
                     continue;
@@ -314,6 +442,8 @@ where
         PreparedImage {
             buffer: buf,
             cache: LruCache::new(NonZeroUsize::new(251).unwrap()),
+            uncertain_bitmap: Vec::new(),
+            inverted: false,
         }
     }
 
@@ -402,6 +532,22 @@ where
         self.buffer.get_pixel(x, y).into()
     }
 
+    /// Whether the pixel at the point was close to the binarization threshold
+    ///
+    /// Points outside the image are always uncertain. Inside, pixels are only
+    /// known to be uncertain if the image was prepared with
+    /// [`prepare_with_erasures`](Self::prepare_with_erasures).
+    pub(crate) fn is_uncertain_at_point(&self, p: Point) -> bool {
+        if p.x < 0 || p.y < 0 || p.x as usize >= self.width() || p.y as usize >= self.height() {
+            return true;
+        }
+        if self.uncertain_bitmap.is_empty() {
+            return false;
+        }
+        let c = p.y as usize * self.width() + p.x as usize;
+        self.uncertain_bitmap[c >> 3] & (1 << (c & 7)) != 0
+    }
+
     #[cfg(feature = "img")]
     pub fn write_state_to(&self, p: &str) {
         let mut dyn_img = image::RgbImage::new(self.width() as u32, self.height() as u32);
@@ -568,6 +714,8 @@ mod tests {
         PreparedImage {
             buffer,
             cache: LruCache::new(NonZeroUsize::new(251).unwrap()),
+            uncertain_bitmap: Vec::new(),
+            inverted: false,
         }
     }
 
@@ -0,0 +1,277 @@
+//! Rendering of grids
+//!
+//! Any [`BitGrid`] can be rendered as SVG, as a grayscale image or as
+//! Unicode text for terminals. All outputs add a quiet zone of light modules
+//! around the grid.
+
+use alloc::string::String;
+use core::fmt::Write;
+#[cfg(feature = "png")]
+use std::path::Path;
+
+use crate::BitGrid;
+
+/// Render grids for people to look at
+///
+/// Implemented for every [`BitGrid`], e.g. to see what was sampled from an
+/// image. Images need the `img` feature, saving them as PNG the `png`
+/// feature.
+///
+/// # Example
+///
+/// ```rust
+/// use rqrr::Render;
+///
+/// # fn main() -> Result<(), Box<dyn ::std::error::Error>> {
+/// let grid = rqrr::SimpleGrid::encode(b"rqrr", 0)?;
+/// let svg = grid.to_svg(&rqrr::RenderOptions::default());
+/// assert!(svg.starts_with("<svg"));
+/// # Ok(())
+/// # }
+/// ```
+pub trait Render: BitGrid {
+    /// Render the grid as an SVG document.
+    ///
+    /// Dark modules are drawn as a single path on a light background of the
+    /// size of the grid and its quiet zone.
+    fn to_svg(&self, options: &RenderOptions) -> String {
+        svg(self, options)
+    }
+
+    /// Render the grid as text for terminals.
+    ///
+    /// Every character covers one module horizontally and two vertically,
+    /// using Unicode half blocks for dark modules. On terminals with a dark
+    /// background, set [`RenderOptions::invert`] so that the code is shown
+    /// dark on light.
+    fn to_unicode(&self, options: &RenderOptions) -> String {
+        unicode(self, options)
+    }
+
+    /// Render the grid as a grayscale image.
+    ///
+    /// Every module is drawn as a square of [`RenderOptions::module_size`]
+    /// pixels.
+    #[cfg(feature = "img")]
+    fn to_image(&self, options: &RenderOptions) -> image::GrayImage {
+        image(self, options)
+    }
+
+    /// Save the grid as a PNG image.
+    ///
+    /// The image is rendered like in [`to_image`](Self::to_image).
+    #[cfg(feature = "png")]
+    fn save_png(&self, path: &Path, options: &RenderOptions) -> image::ImageResult<()> {
+        image(self, options).save_with_format(path, image::ImageFormat::Png)
+    }
+}
+
+impl<G> Render for G where G: BitGrid + ?Sized {}
+
+/// Options for rendering a grid, see [`Render`]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct RenderOptions {
+    /// Width of the light border around the grid, in modules
+    ///
+    /// Scanners need at least 4 modules for regular QR codes and 2 for Micro
+    /// and rMQR codes.
+    pub quiet_zone: usize,
+    /// Size of a module in pixels, for images and the size of SVG documents
+    pub module_size: usize,
+    /// Swap dark and light modules, including the quiet zone
+    pub invert: bool,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            quiet_zone: 4,
+            module_size: 8,
+            invert: false,
+        }
+    }
+}
+
+/// Return whether the module at the given position, including the quiet
+/// zone, is drawn dark
+fn dark<G>(grid: &G, options: &RenderOptions, y: usize, x: usize) -> bool
+where
+    G: BitGrid + ?Sized,
+{
+    let q = options.quiet_zone;
+    let inside = (q..q + grid.height()).contains(&y) && (q..q + grid.width()).contains(&x);
+    (inside && grid.bit(y - q, x - q)) != options.invert
+}
+
+pub(crate) fn svg<G>(grid: &G, options: &RenderOptions) -> String
+where
+    G: BitGrid + ?Sized,
+{
+    let width = grid.width() + 2 * options.quiet_zone;
+    let height = grid.height() + 2 * options.quiet_zone;
+    let (fg, bg) = match options.invert {
+        false => ("#000", "#fff"),
+        true => ("#fff", "#000"),
+    };
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}" shape-rendering="crispEdges">"#,
+        width * options.module_size,
+        height * options.module_size,
+        width,
+        height,
+    )
+    .unwrap();
+    writeln!(
+        out,
+        r#"<rect width="{}" height="{}" fill="{}"/>"#,
+        width, height, bg
+    )
+    .unwrap();
+
+    /* Draw horizontal runs of dark modules in the foreground color, which
+     * is light for inverted colors.
+     */
+    write!(out, r#"<path fill="{}" d=""#, fg).unwrap();
+    for y in 0..grid.height() {
+        let mut x = 0;
+        while x < grid.width() {
+            let start = x;
+            while x < grid.width() && grid.bit(y, x) {
+                x += 1;
+            }
+            if x > start {
+                write!(
+                    out,
+                    "M{},{}h{}v1h-{}z",
+                    start + options.quiet_zone,
+                    y + options.quiet_zone,
+                    x - start,
+                    x - start
+                )
+                .unwrap();
+            } else {
+                x += 1;
+            }
+        }
+    }
+    out.push_str("\"/>\n</svg>\n");
+    out
+}
+
+pub(crate) fn unicode<G>(grid: &G, options: &RenderOptions) -> String
+where
+    G: BitGrid + ?Sized,
+{
+    let width = grid.width() + 2 * options.quiet_zone;
+    let height = grid.height() + 2 * options.quiet_zone;
+
+    let mut out = String::new();
+    for y in (0..height).step_by(2) {
+        for x in 0..width {
+            let top = dark(grid, options, y, x);
+            let bottom = y + 1 < height && dark(grid, options, y + 1, x);
+            out.push(match (top, bottom) {
+                (true, true) => '\u{2588}',
+                (true, false) => '\u{2580}',
+                (false, true) => '\u{2584}',
+                (false, false) => ' ',
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(feature = "img")]
+pub(crate) fn image<G>(grid: &G, options: &RenderOptions) -> image::GrayImage
+where
+    G: BitGrid + ?Sized,
+{
+    let scale = options.module_size.max(1);
+    let width = (grid.width() + 2 * options.quiet_zone) * scale;
+    let height = (grid.height() + 2 * options.quiet_zone) * scale;
+    image::GrayImage::from_fn(width as u32, height as u32, |x, y| {
+        match dark(grid, options, y as usize / scale, x as usize / scale) {
+            true => image::Luma([0]),
+            false => image::Luma([255]),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SimpleGrid;
+
+    fn diagonal() -> SimpleGrid {
+        SimpleGrid::from_func_rect(3, 2, |x, y| x == y || x == 2)
+    }
+
+    #[test]
+    fn test_svg() {
+        let options = RenderOptions {
+            quiet_zone: 1,
+            module_size: 2,
+            invert: false,
+        };
+        let svg = svg(&diagonal(), &options);
+        assert!(svg.starts_with(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="10" height="8" viewBox="0 0 5 4""#
+        ));
+        assert!(svg.contains(r##"<path fill="#000" d="M1,1h1v1h-1zM3,1h1v1h-1zM2,2h2v1h-2z"/>"##));
+    }
+
+    #[test]
+    fn test_unicode() {
+        let options = RenderOptions {
+            quiet_zone: 1,
+            ..RenderOptions::default()
+        };
+        assert_eq!(
+            unicode(&diagonal(), &options),
+            " \u{2584} \u{2584} \n  \u{2580}\u{2580} \n"
+        );
+
+        let options = RenderOptions {
+            quiet_zone: 0,
+            invert: true,
+            ..RenderOptions::default()
+        };
+        assert_eq!(unicode(&diagonal(), &options), "\u{2584}\u{2580} \n");
+    }
+
+    #[cfg(feature = "img")]
+    #[test]
+    fn test_image_round_trip() {
+        let simple = SimpleGrid::encode(b"https://github.com/WanzenBug/rqrr", 0).unwrap();
+        let img = image(&simple, &RenderOptions::default());
+        assert_eq!(img.dimensions(), (29 * 8 + 64, 29 * 8 + 64));
+        assert_eq!(img.get_pixel(31, 31).0, [255]);
+        assert_eq!(img.get_pixel(32, 32).0, [0]);
+
+        let mut img = crate::PreparedImage::prepare(img);
+        let grids = img.detect_grids();
+        assert_eq!(grids.len(), 1);
+        let (_, content) = grids[0].decode().unwrap();
+        assert_eq!(content, "https://github.com/WanzenBug/rqrr");
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn test_save_png() {
+        let simple = SimpleGrid::encode(b"rqrr", 0).unwrap();
+        let path = std::env::temp_dir().join("rqrr_test_save_png.png");
+        simple.save_png(&path, &RenderOptions::default()).unwrap();
+        let img = ::image::open(&path).unwrap().to_luma8();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(img, image(&simple, &RenderOptions::default()));
+
+        let missing = std::env::temp_dir().join("rqrr_missing_dir/code.png");
+        assert!(simple
+            .save_png(&missing, &RenderOptions::default())
+            .is_err());
+    }
+}
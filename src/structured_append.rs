@@ -0,0 +1,254 @@
+//! Structured Append sequences
+//!
+//! A message can be split over up to 16 QR codes. Every symbol in such a
+//! sequence starts with a header giving its position, the number of symbols and
+//! a parity byte computed over the complete message.
+
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+use crate::charset::TextDecoder;
+use crate::decode::{Mode, PayloadSink, Segment};
+use crate::{BitGrid, DeQRError, DeQRResult, Grid};
+
+/// Position of a symbol in a Structured Append sequence
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct StructuredAppend {
+    /// Index of this symbol in the sequence, starting at 0
+    pub index: u8,
+    /// Total number of symbols in the sequence, between 1 and 16
+    pub total: u8,
+    /// XOR of all data bytes of the complete message
+    pub parity: u8,
+}
+
+/// Collects the symbols of a Structured Append sequence
+///
+/// Symbols can be added in any order, for example from several images. Once
+/// all symbols have arrived, their content is joined and the parity is
+/// checked.
+///
+/// A symbol added again replaces the one stored for its index. So if the
+/// parity does not match because a symbol was misread, adding another read of
+/// that symbol completes the sequence. To start over, for example when the
+/// first symbol added belongs to an unwanted sequence, call
+/// [`clear`](Self::clear).
+///
+/// # Example
+///
+/// ```rust
+/// # fn main() -> Result<(), rqrr::DeQRError> {
+/// # let grids: Vec<rqrr::Grid<rqrr::SimpleGrid>> = Vec::new();
+/// // Grids detected in one or more images
+/// let mut reassembler = rqrr::Reassembler::new();
+/// for grid in &grids {
+///     if let Some(content) = reassembler.add(grid)? {
+///         println!("{}", content);
+///     }
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Reassembler {
+    header: Option<StructuredAppend>,
+    parts: Vec<Option<Vec<Segment>>>,
+}
+
+impl Reassembler {
+    /// Create a new, empty reassembler
+    pub fn new() -> Self {
+        Reassembler::default()
+    }
+
+    /// Decode a grid and add it to the sequence
+    ///
+    /// Returns the joined content once all symbols have been added. Fails with
+    /// [`DeQRError::StructuredAppend`] if the grid is not part of the same
+    /// sequence as the ones added before, or if the parity of the complete
+    /// message does not match. The grid replaces any symbol added before with
+    /// the same index.
+    pub fn add<G>(&mut self, grid: &Grid<G>) -> DeQRResult<Option<String>>
+    where
+        G: BitGrid,
+    {
+        let (meta, segments) = grid.decode_segments()?;
+        let header = meta.structured_append.ok_or(DeQRError::StructuredAppend)?;
+        self.add_segments(header, segments)
+    }
+
+    /// Remove all symbols, to collect a new sequence
+    pub fn clear(&mut self) {
+        self.header = None;
+        self.parts.clear();
+    }
+
+    /// Indices of the symbols that are still missing
+    pub fn missing(&self) -> Vec<u8> {
+        match self.header {
+            None => Vec::new(),
+            Some(_) => (0..self.parts.len())
+                .filter(|&i| self.parts[i].is_none())
+                .map(|i| i as u8)
+                .collect(),
+        }
+    }
+
+    fn add_segments(
+        &mut self,
+        header: StructuredAppend,
+        segments: Vec<Segment>,
+    ) -> DeQRResult<Option<String>> {
+        match self.header {
+            None => {
+                self.header = Some(header);
+                self.parts = vec![None; header.total as usize];
+            }
+            Some(h) if h.total != header.total || h.parity != header.parity => {
+                return Err(DeQRError::StructuredAppend)
+            }
+            Some(_) => (),
+        }
+
+        let part = self
+            .parts
+            .get_mut(header.index as usize)
+            .ok_or(DeQRError::StructuredAppend)?;
+        *part = Some(segments);
+
+        if !self.missing().is_empty() {
+            return Ok(None);
+        }
+
+        let mut parity = 0;
+        let mut text = TextDecoder::new();
+        for segment in self.parts.iter().flatten().flatten() {
+            match segment.mode {
+                Mode::Eci => text.set_eci(segment.eci.expect("ECI segments have an ECI"))?,
+                Mode::StructuredAppend => (),
+                mode => {
+                    parity = segment.data.iter().fold(parity, |p, b| p ^ b);
+                    text.write_bytes(mode, &segment.data)?;
+                }
+            }
+        }
+        if Some(parity) != self.header.map(|h| h.parity) {
+            return Err(DeQRError::StructuredAppend);
+        }
+
+        Ok(Some(text.finish()?.text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::string::ToString;
+
+    fn part(index: u8, total: u8, parity: u8, data: &[u8]) -> (StructuredAppend, Vec<Segment>) {
+        let header = StructuredAppend {
+            index,
+            total,
+            parity,
+        };
+        let segments = vec![
+            Segment {
+                mode: Mode::StructuredAppend,
+                count: 0,
+                bit_offset: 0,
+                eci: None,
+                data: Vec::new(),
+            },
+            Segment {
+                mode: Mode::Byte,
+                count: data.len(),
+                bit_offset: 20,
+                eci: None,
+                data: data.to_vec(),
+            },
+        ];
+        (header, segments)
+    }
+
+    fn parity(data: &[u8]) -> u8 {
+        data.iter().fold(0, |p, b| p ^ b)
+    }
+
+    #[test]
+    fn test_reassemble_out_of_order() {
+        let p = parity(b"Hello, World!");
+        let mut reassembler = Reassembler::new();
+
+        let (h, s) = part(2, 3, p, b"ld!");
+        assert_eq!(reassembler.add_segments(h, s), Ok(None));
+        assert_eq!(reassembler.missing(), vec![0, 1]);
+
+        let (h, s) = part(0, 3, p, b"Hello");
+        assert_eq!(reassembler.add_segments(h, s), Ok(None));
+        // Duplicates from another image replace the stored symbol
+        let (h, s) = part(0, 3, p, b"Hello");
+        assert_eq!(reassembler.add_segments(h, s), Ok(None));
+        assert_eq!(reassembler.missing(), vec![1]);
+
+        let (h, s) = part(1, 3, p, b", Wor");
+        assert_eq!(
+            reassembler.add_segments(h, s),
+            Ok(Some("Hello, World!".to_string()))
+        );
+        assert!(reassembler.missing().is_empty());
+    }
+
+    #[test]
+    fn test_reassemble_parity_mismatch() {
+        let p = parity(b"Hello");
+        let mut reassembler = Reassembler::new();
+
+        let (h, s) = part(0, 2, p, b"Hel");
+        assert_eq!(reassembler.add_segments(h, s), Ok(None));
+        let (h, s) = part(1, 2, p, b"xo");
+        assert_eq!(
+            reassembler.add_segments(h, s),
+            Err(DeQRError::StructuredAppend)
+        );
+        assert!(reassembler.missing().is_empty());
+
+        // Another read of the bad symbol replaces it
+        let (h, s) = part(1, 2, p, b"lo");
+        assert_eq!(
+            reassembler.add_segments(h, s),
+            Ok(Some("Hello".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_reassemble_clear() {
+        let mut reassembler = Reassembler::new();
+        let (h, s) = part(0, 2, 0x12, b"a");
+        assert_eq!(reassembler.add_segments(h, s), Ok(None));
+        reassembler.clear();
+        assert!(reassembler.missing().is_empty());
+
+        // A sequence with a different header can be collected after clearing
+        let (h, s) = part(0, 1, parity(b"b"), b"b");
+        assert_eq!(reassembler.add_segments(h, s), Ok(Some("b".to_string())));
+    }
+
+    #[test]
+    fn test_reassemble_other_sequence() {
+        let mut reassembler = Reassembler::new();
+
+        let (h, s) = part(0, 2, 0x12, b"a");
+        assert_eq!(reassembler.add_segments(h, s), Ok(None));
+        let (h, s) = part(1, 3, 0x12, b"b");
+        assert_eq!(
+            reassembler.add_segments(h, s),
+            Err(DeQRError::StructuredAppend)
+        );
+        let (h, s) = part(1, 2, 0x34, b"b");
+        assert_eq!(
+            reassembler.add_segments(h, s),
+            Err(DeQRError::StructuredAppend)
+        );
+    }
+}
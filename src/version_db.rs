@@ -1070,3 +1070,1398 @@ pub const VERSION_DATA_BASE: [VersionInfo; 41] = [
         ],
     },
 ];
+
+/* ***********************************************************************
+ * QR-code Model 1 version information database
+ *
+ * Indexed by the version 1 to 14. All blocks of a symbol have the same size.
+ * Codewords left over after the last block are remainder codewords and do
+ * not carry any data.
+ */
+pub const MODEL1_VERSION_DATA_BASE: [VersionInfo; 15] = [
+    VersionInfo {
+        data_bytes: 0,
+        apat: [0; 7],
+        ecc: [RSParameters {
+            bs: 0,
+            dw: 0,
+            ns: 0,
+        }; 4],
+    },
+    VersionInfo {
+        data_bytes: 26,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 26,
+                dw: 16,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 26,
+                dw: 19,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 26,
+                dw: 9,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 26,
+                dw: 13,
+                ns: 1,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 46,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 46,
+                dw: 30,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 46,
+                dw: 36,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 46,
+                dw: 16,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 46,
+                dw: 24,
+                ns: 1,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 72,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 72,
+                dw: 44,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 72,
+                dw: 57,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 72,
+                dw: 24,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 72,
+                dw: 36,
+                ns: 1,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 100,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 100,
+                dw: 60,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 100,
+                dw: 80,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 100,
+                dw: 34,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 100,
+                dw: 50,
+                ns: 1,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 134,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 134,
+                dw: 82,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 134,
+                dw: 108,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 67,
+                dw: 23,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 134,
+                dw: 68,
+                ns: 1,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 170,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 85,
+                dw: 53,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 170,
+                dw: 136,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 85,
+                dw: 29,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 85,
+                dw: 43,
+                ns: 2,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 212,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 106,
+                dw: 66,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 212,
+                dw: 170,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 70,
+                dw: 24,
+                ns: 3,
+            },
+            RSParameters {
+                bs: 106,
+                dw: 54,
+                ns: 2,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 256,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 128,
+                dw: 80,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 128,
+                dw: 104,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 85,
+                dw: 29,
+                ns: 3,
+            },
+            RSParameters {
+                bs: 128,
+                dw: 64,
+                ns: 2,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 306,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 153,
+                dw: 93,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 153,
+                dw: 123,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 102,
+                dw: 34,
+                ns: 3,
+            },
+            RSParameters {
+                bs: 102,
+                dw: 52,
+                ns: 3,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 358,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 179,
+                dw: 111,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 179,
+                dw: 145,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 89,
+                dw: 31,
+                ns: 4,
+            },
+            RSParameters {
+                bs: 119,
+                dw: 61,
+                ns: 3,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 416,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 104,
+                dw: 64,
+                ns: 4,
+            },
+            RSParameters {
+                bs: 208,
+                dw: 168,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 83,
+                dw: 29,
+                ns: 5,
+            },
+            RSParameters {
+                bs: 104,
+                dw: 52,
+                ns: 4,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 476,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 119,
+                dw: 73,
+                ns: 4,
+            },
+            RSParameters {
+                bs: 238,
+                dw: 192,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 95,
+                dw: 33,
+                ns: 5,
+            },
+            RSParameters {
+                bs: 119,
+                dw: 61,
+                ns: 4,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 542,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 135,
+                dw: 83,
+                ns: 4,
+            },
+            RSParameters {
+                bs: 180,
+                dw: 144,
+                ns: 3,
+            },
+            RSParameters {
+                bs: 90,
+                dw: 32,
+                ns: 6,
+            },
+            RSParameters {
+                bs: 135,
+                dw: 69,
+                ns: 4,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 610,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 152,
+                dw: 92,
+                ns: 4,
+            },
+            RSParameters {
+                bs: 203,
+                dw: 163,
+                ns: 3,
+            },
+            RSParameters {
+                bs: 101,
+                dw: 35,
+                ns: 6,
+            },
+            RSParameters {
+                bs: 122,
+                dw: 62,
+                ns: 5,
+            },
+        ],
+    },
+];
+
+/* ***********************************************************************
+ * Micro QR-code version information database
+ *
+ * Indexed by the version M1 to M4. Micro QR codes always use a single block
+ * and support only some of the error correction levels, the others are left
+ * empty. In M1 and M3, the last data word is only 4 bits long.
+ */
+pub const MICRO_VERSION_DATA_BASE: [VersionInfo; 5] = [
+    VersionInfo {
+        data_bytes: 0,
+        apat: [0; 7],
+        ecc: [RSParameters {
+            bs: 0,
+            dw: 0,
+            ns: 0,
+        }; 4],
+    },
+    VersionInfo {
+        data_bytes: 5,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 5,
+                dw: 3,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 10,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 10,
+                dw: 4,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 10,
+                dw: 5,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 17,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 17,
+                dw: 9,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 17,
+                dw: 11,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 24,
+        apat: [0; 7],
+        ecc: [
+            RSParameters {
+                bs: 24,
+                dw: 14,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 24,
+                dw: 16,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 24,
+                dw: 10,
+                ns: 1,
+            },
+        ],
+    },
+];
+
+/* ***********************************************************************
+ * Rectangular Micro QR-code (rMQR) version information database
+ *
+ * Indexed by the version R7x43 (1) to R17x139 (32). rMQR codes only support
+ * the error correction levels M and H, the others are left empty. Instead of
+ * the positions of alignment patterns, `apat` lists the columns of the
+ * vertical timing patterns, which have an alignment pattern at either end.
+ */
+pub const RMQR_VERSION_DATA_BASE: [VersionInfo; 33] = [
+    VersionInfo {
+        data_bytes: 0,
+        apat: [0; 7],
+        ecc: [RSParameters {
+            bs: 0,
+            dw: 0,
+            ns: 0,
+        }; 4],
+    },
+    VersionInfo {
+        data_bytes: 13,
+        apat: [21, 0, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 13,
+                dw: 6,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 13,
+                dw: 3,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 21,
+        apat: [19, 39, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 21,
+                dw: 12,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 21,
+                dw: 7,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 32,
+        apat: [25, 51, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 32,
+                dw: 20,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 32,
+                dw: 10,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 44,
+        apat: [23, 49, 75, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 44,
+                dw: 28,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 44,
+                dw: 14,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 68,
+        apat: [27, 55, 83, 111, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 68,
+                dw: 44,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 34,
+                dw: 12,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 21,
+        apat: [21, 0, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 21,
+                dw: 12,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 21,
+                dw: 7,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 33,
+        apat: [19, 39, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 33,
+                dw: 21,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 33,
+                dw: 11,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 49,
+        apat: [25, 51, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 49,
+                dw: 31,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 24,
+                dw: 8,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 66,
+        apat: [23, 49, 75, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 66,
+                dw: 42,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 33,
+                dw: 11,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 99,
+        apat: [27, 55, 83, 111, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 49,
+                dw: 31,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 33,
+                dw: 11,
+                ns: 3,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 15,
+        apat: [0, 0, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 15,
+                dw: 7,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 15,
+                dw: 5,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 31,
+        apat: [21, 0, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 31,
+                dw: 19,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 31,
+                dw: 11,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 47,
+        apat: [19, 39, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 47,
+                dw: 31,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 23,
+                dw: 7,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 67,
+        apat: [25, 51, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 67,
+                dw: 43,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 33,
+                dw: 11,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 89,
+        apat: [23, 49, 75, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 44,
+                dw: 28,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 44,
+                dw: 14,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 132,
+        apat: [27, 55, 83, 111, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 66,
+                dw: 42,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 44,
+                dw: 14,
+                ns: 3,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 21,
+        apat: [0, 0, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 21,
+                dw: 12,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 21,
+                dw: 7,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 41,
+        apat: [21, 0, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 41,
+                dw: 27,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 41,
+                dw: 13,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 60,
+        apat: [19, 39, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 60,
+                dw: 38,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 30,
+                dw: 10,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 85,
+        apat: [25, 51, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 42,
+                dw: 26,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 42,
+                dw: 14,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 113,
+        apat: [23, 49, 75, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 56,
+                dw: 36,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 37,
+                dw: 11,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 166,
+        apat: [27, 55, 83, 111, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 55,
+                dw: 35,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 41,
+                dw: 13,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 51,
+        apat: [21, 0, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 51,
+                dw: 33,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 25,
+                dw: 7,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 74,
+        apat: [19, 39, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 74,
+                dw: 48,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 37,
+                dw: 13,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 103,
+        apat: [25, 51, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 51,
+                dw: 33,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 34,
+                dw: 10,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 136,
+        apat: [23, 49, 75, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 68,
+                dw: 44,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 34,
+                dw: 12,
+                ns: 4,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 199,
+        apat: [27, 55, 83, 111, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 66,
+                dw: 42,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 39,
+                dw: 13,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 61,
+        apat: [21, 0, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 61,
+                dw: 39,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 30,
+                dw: 10,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 88,
+        apat: [19, 39, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 44,
+                dw: 28,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 44,
+                dw: 14,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 122,
+        apat: [25, 51, 0, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 61,
+                dw: 39,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 40,
+                dw: 12,
+                ns: 1,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 160,
+        apat: [23, 49, 75, 0, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 53,
+                dw: 33,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 40,
+                dw: 14,
+                ns: 4,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+    VersionInfo {
+        data_bytes: 232,
+        apat: [27, 55, 83, 111, 0, 0, 0],
+        ecc: [
+            RSParameters {
+                bs: 58,
+                dw: 38,
+                ns: 4,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+            RSParameters {
+                bs: 38,
+                dw: 12,
+                ns: 2,
+            },
+            RSParameters {
+                bs: 0,
+                dw: 0,
+                ns: 0,
+            },
+        ],
+    },
+];
+
+/// Width and height of rMQR codes, indexed by version
+pub const RMQR_SIZES: [(usize, usize); 33] = [
+    (0, 0),
+    (43, 7),
+    (59, 7),
+    (77, 7),
+    (99, 7),
+    (139, 7),
+    (43, 9),
+    (59, 9),
+    (77, 9),
+    (99, 9),
+    (139, 9),
+    (27, 11),
+    (43, 11),
+    (59, 11),
+    (77, 11),
+    (99, 11),
+    (139, 11),
+    (27, 13),
+    (43, 13),
+    (59, 13),
+    (77, 13),
+    (99, 13),
+    (139, 13),
+    (43, 15),
+    (59, 15),
+    (77, 15),
+    (99, 15),
+    (139, 15),
+    (43, 17),
+    (59, 17),
+    (77, 17),
+    (99, 17),
+    (139, 17),
+];
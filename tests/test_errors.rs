@@ -1,6 +1,6 @@
 #![cfg(feature = "img")]
 
-use rqrr::{DeQRError, PreparedImage};
+use rqrr::{DeQRError, IoWriter, PreparedImage};
 
 use std::io::{Error, ErrorKind, Write};
 
@@ -34,7 +34,7 @@ fn test_io_error() {
 
     let writer = BrokenWriter {};
 
-    let result = grids[0].decode_to(writer);
+    let result = grids[0].decode_to(IoWriter(writer));
     assert!(result.is_err());
     let err = result.unwrap_err();
     assert_eq!(err, DeQRError::IoError);
@@ -53,6 +53,8 @@ fn test_invalid_version() {
 
 #[test]
 fn test_format_ecc() {
+    // Both copies of the format information are wiped, the format is recovered
+    // by trying the closest candidates
     let img = image::open("tests/data/errors/format_ecc.png")
         .unwrap()
         .to_luma8();
@@ -61,12 +63,10 @@ fn test_format_ecc() {
     let grids = search_img.detect_grids();
     assert_eq!(grids.len(), 1);
 
-    let decoded = grids[0].decode();
-    println!("{:?}", decoded);
-    assert!(decoded.is_err());
-
-    let err = decoded.unwrap_err();
-    assert_eq!(err, DeQRError::FormatEcc);
+    let (meta, content) = grids[0].decode().unwrap();
+    assert_eq!(meta.ecc_level, 0);
+    assert_eq!(meta.mask, 6);
+    assert_eq!(content, "Format info wiped");
 }
 
 #[test]
@@ -83,6 +83,70 @@ fn test_full_multi_rotated() {
     assert_eq!(content, "odo consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur. Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt mollit anim id est laborum.");
 }
 
+#[test]
+fn test_full_damaged_timing() {
+    // Gaps in the timing pattern make it look like a version 8 code, the
+    // version information tells otherwise
+    let (meta, content) = decode_single("tests/data/full/damaged_timing.png", rqrr::SymbolKind::Qr);
+    assert_eq!(meta.version, rqrr::Version(7));
+    assert_eq!(content, "Version information: 7");
+}
+
+#[test]
+fn test_full_short_timing() {
+    // Light timing modules painted dark make the version 7 code look like
+    // version 5, too small to carry version information
+    use rqrr::BitGrid;
+
+    let data = "Version information".repeat(6);
+    let grid = rqrr::SimpleGrid::encode(data.as_bytes(), 0).unwrap();
+    assert_eq!(grid.size(), 45);
+    let module = |x: usize, y: usize| match (x, y) {
+        (9 | 11 | 13 | 15, 6) | (6, 9 | 11 | 13 | 15) => true,
+        _ => grid.bit(y, x),
+    };
+
+    let (scale, quiet) = (4, 4);
+    let size = (grid.size() + 2 * quiet) * scale;
+    let mut search_img = rqrr::PreparedImage::prepare_from_bitmap(size, size, |x, y| {
+        let (x, y) = (x / scale, y / scale);
+        let inside =
+            (quiet..quiet + grid.size()).contains(&x) && (quiet..quiet + grid.size()).contains(&y);
+        inside && module(x - quiet, y - quiet)
+    });
+    let grids = search_img.detect_grids();
+    assert_eq!(grids.len(), 1);
+
+    let (meta, content) = grids[0].decode().unwrap();
+    assert_eq!(meta.version, rqrr::Version(7));
+    assert_eq!(content, data);
+}
+
+#[test]
+fn test_full_hinted_version() {
+    // Without version information, the damaged timing pattern can only be
+    // corrected with a hint
+    let img = image::open("tests/data/full/damaged_timing_v5.png")
+        .unwrap()
+        .to_luma8();
+    let mut search_img = rqrr::PreparedImage::prepare(img);
+    let grids = search_img.detect_grids();
+    assert_eq!(grids.len(), 1);
+    assert!(grids[0].decode().is_err());
+
+    let hints = rqrr::DecodeHints {
+        version: Some(rqrr::Version(5)),
+        ..Default::default()
+    };
+    let (meta, content) = grids[0].decode_with_hints(&hints).unwrap();
+    assert_eq!(meta.version, rqrr::Version(5));
+    assert_eq!(content, "Hinted version 5");
+
+    // The hints can be taken from a previously decoded code
+    let (meta, _) = grids[0].decode_with_hints(&(&meta).into()).unwrap();
+    assert_eq!(meta.ecc_level, 3);
+}
+
 #[test]
 fn test_mirrored() {
     let img = image::open("tests/data/mirrored.gif").unwrap().to_luma8();
@@ -95,5 +159,193 @@ fn test_mirrored() {
     assert_eq!(meta.version, rqrr::Version(1));
     assert_eq!(meta.ecc_level, 0);
     assert_eq!(meta.mask, 0);
+    assert!(meta.mirrored);
     assert_eq!(raw, "rqrr");
+
+    // The grid and its bounds are in the true orientation of the code, with
+    // the top right corner below the top left one in the image
+    assert!(grids[0].is_mirrored());
+    assert_eq!(grids[0].bounds[0], rqrr::Point { x: 8, y: 8 });
+    assert_eq!(grids[0].bounds[1], rqrr::Point { x: 8, y: 96 });
+    let top_right = grids[0].grid.module_position(0, 20);
+    assert!(top_right.x < 16 && top_right.y > 88);
+}
+
+#[test]
+fn test_segments() {
+    let img = image::open("tests/data/number.gif").unwrap().to_luma8();
+
+    let mut search_img = rqrr::PreparedImage::prepare(img);
+    let grids = search_img.detect_grids();
+    assert_eq!(grids.len(), 1);
+
+    let (_meta, segments) = grids[0].decode_segments().unwrap();
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].mode, rqrr::Mode::Byte);
+    assert_eq!(segments[0].count, 31);
+    assert_eq!(segments[0].bit_offset, 0);
+    assert_eq!(segments[0].eci, None);
+    assert_eq!(segments[0].data, b"1234567891011121314151617181920");
+}
+
+fn decode_single(path: &str, kind: rqrr::SymbolKind) -> (rqrr::MetaData, String) {
+    let img = image::open(path).unwrap().to_luma8();
+
+    let mut search_img = rqrr::PreparedImage::prepare(img);
+    let grids = search_img.detect_grids();
+    assert_eq!(grids.len(), 1);
+
+    let (meta, content) = grids[0].decode().unwrap();
+    assert_eq!(meta.kind, kind);
+    (meta, content)
+}
+
+#[test]
+fn test_micro() {
+    let (meta, content) = decode_single("tests/data/micro/m1.png", rqrr::SymbolKind::Micro);
+    assert_eq!(meta.version, rqrr::Version(1));
+    assert_eq!(content, "12345");
+
+    let (meta, content) = decode_single("tests/data/micro/m2.png", rqrr::SymbolKind::Micro);
+    assert_eq!(meta.version, rqrr::Version(2));
+    assert_eq!(meta.ecc_level, 0);
+    assert_eq!(content, "RQRR");
+
+    // Rotated by 90 degrees
+    let (meta, content) = decode_single("tests/data/micro/m3.png", rqrr::SymbolKind::Micro);
+    assert_eq!(meta.version, rqrr::Version(3));
+    assert_eq!(content, "micro");
+
+    let (meta, content) = decode_single("tests/data/micro/m4.png", rqrr::SymbolKind::Micro);
+    assert_eq!(meta.version, rqrr::Version(4));
+    assert_eq!(meta.ecc_level, 1);
+    assert_eq!(content, "WanzenBug/rqrr");
+}
+
+#[test]
+fn test_rmqr() {
+    let (meta, content) = decode_single("tests/data/rmqr/r7x43.png", rqrr::SymbolKind::Rmqr);
+    assert_eq!(meta.version, rqrr::Version(1));
+    assert_eq!(meta.ecc_level, 0);
+    assert_eq!(content, "ABCDEFG");
+
+    // Rotated by 90 degrees
+    let (meta, content) = decode_single("tests/data/rmqr/r11x27.png", rqrr::SymbolKind::Rmqr);
+    assert_eq!(meta.version.to_rmqr_size(), (27, 11));
+    assert_eq!(meta.ecc_level, 2);
+    assert_eq!(content, "ABCDEF");
+
+    let (meta, content) = decode_single("tests/data/rmqr/r17x99.png", rqrr::SymbolKind::Rmqr);
+    assert_eq!(meta.version.to_rmqr_size(), (99, 17));
+    assert_eq!(
+        content,
+        "1234567890ABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890________________________"
+    );
+
+    // Rotated by 180 degrees
+    let (meta, content) = decode_single("tests/data/rmqr/r7x139.png", rqrr::SymbolKind::Rmqr);
+    assert_eq!(meta.version, rqrr::Version(5));
+    assert_eq!(content, "1234567890,ABCDEFGHIJKLMOPQRSTUVW");
+}
+
+#[test]
+fn test_full_inverted() {
+    let png = image::open("tests/data/full/inverted.png")
+        .unwrap()
+        .to_luma8();
+
+    let mut search_img = rqrr::PreparedImage::prepare(png);
+    let grids = search_img.detect_grids();
+    assert_eq!(grids.len(), 1);
+    assert!(!grids[0].is_inverted());
+    let (_meta, content) = grids[0].decode().unwrap();
+    assert_eq!(content, "Dark on light");
+
+    let grids = search_img.detect_grids_inverted();
+    assert_eq!(grids.len(), 1);
+    assert!(grids[0].is_inverted());
+    let (_meta, content) = grids[0].decode().unwrap();
+    assert_eq!(content, "Light on dark");
+
+    // Searching again undoes the swap
+    let grids = search_img.detect_grids();
+    assert_eq!(grids.len(), 1);
+    let (_meta, content) = grids[0].decode().unwrap();
+    assert_eq!(content, "Dark on light");
+}
+
+#[test]
+fn test_model1() {
+    let (meta, content) = decode_single("tests/data/model1/v1.png", rqrr::SymbolKind::Model1);
+    assert_eq!(meta.version, rqrr::Version(1));
+    assert_eq!(content, "Model 1");
+    assert_eq!(meta.unused_error_correction(), Some(1.0));
+
+    // Rotated by 90 degrees, with some modules flipped
+    let (meta, content) = decode_single("tests/data/model1/v4.png", rqrr::SymbolKind::Model1);
+    assert_eq!(meta.version, rqrr::Version(4));
+    assert_eq!(meta.ecc_level, 2);
+    assert_eq!(content, "Legacy label 0042");
+    assert!(meta.ecc_blocks.iter().map(|b| b.corrected).sum::<usize>() > 0);
+
+    // Rotated by 180 degrees, three blocks
+    let (meta, content) = decode_single("tests/data/model1/v7.png", rqrr::SymbolKind::Model1);
+    assert_eq!(meta.version, rqrr::Version(7));
+    assert_eq!(content, "LOT 77-1138 / PART 4711-B");
+
+    // Rotated by 270 degrees, five blocks
+    let (meta, content) = decode_single("tests/data/model1/v14.png", rqrr::SymbolKind::Model1);
+    assert_eq!(meta.version, rqrr::Version(14));
+    assert_eq!(meta.ecc_level, 3);
+    assert_eq!(
+        content,
+        "QR Model 1 symbol, version 14 with five error correction blocks. ".repeat(4)
+    );
+}
+
+#[test]
+fn test_erasures() {
+    // A grey smudge covers more modules than can be corrected as errors, but
+    // not more than can be corrected as erasures
+    let img = image::open("tests/data/erasures/smudge.png")
+        .unwrap()
+        .to_luma8();
+    let mut search_img = rqrr::PreparedImage::prepare(img.clone());
+    let grids = search_img.detect_grids();
+    assert_eq!(grids.len(), 1);
+    assert_eq!(grids[0].decode().unwrap_err(), rqrr::DeQRError::DataEcc);
+
+    let mut search_img = rqrr::PreparedImage::prepare_with_erasures(img);
+    let grids = search_img.detect_grids();
+    assert_eq!(grids.len(), 1);
+    let (meta, content) = grids[0].decode().unwrap();
+    assert_eq!(meta.version, rqrr::Version(3));
+    assert_eq!(content, "https://github.com/WanzenBug/rqrr");
+    assert_eq!(meta.ecc_blocks.len(), 1);
+    assert!(meta.ecc_blocks[0].corrected > 0);
+    assert!(meta.unused_error_correction().unwrap() < 0.5);
+}
+
+#[test]
+fn test_error_locations() {
+    let img = image::open("tests/data/erasures/smudge.png")
+        .unwrap()
+        .to_luma8();
+    let mut search_img = rqrr::PreparedImage::prepare_with_erasures(img);
+    let grids = search_img.detect_grids();
+    assert_eq!(grids.len(), 1);
+
+    let (meta, corrected) = grids[0].error_locations().unwrap();
+    assert_eq!(corrected.len(), meta.ecc_blocks[0].corrected);
+
+    // The smudge covers modules 9 to 23 horizontally and 10 to 17 vertically,
+    // rendered with 6 pixels per module and a quiet zone of 4 modules
+    for codeword in &corrected {
+        for &(y, x) in &codeword.modules {
+            let p = grids[0].grid.module_position(y, x);
+            assert_eq!((p.x / 6 - 4, p.y / 6 - 4), (x as i32, y as i32));
+        }
+    }
+    let smudged = |&(y, x): &(usize, usize)| (9..24).contains(&x) && (10..18).contains(&y);
+    assert!(corrected.iter().all(|c| c.modules.iter().any(smudged)));
 }
@@ -82,6 +82,7 @@ fn test_small_rqrr() {
         ],
     ];
 
+    #[allow(clippy::needless_range_loop)]
     for x in 0..c.grid.size() {
         for y in 0..c.grid.size() {
             assert_eq!(cmp[y][x] == 1, c.grid.bit(y, x))
@@ -189,6 +190,7 @@ fn test_small_github() {
         ],
     ];
 
+    #[allow(clippy::needless_range_loop)]
     for x in 0..c.grid.size() {
         for y in 0..c.grid.size() {
             assert_eq!(cmp[y][x] == 1, c.grid.bit(y, x))
@@ -295,6 +297,7 @@ fn test_small_number() {
         ],
     ];
 
+    #[allow(clippy::needless_range_loop)]
     for x in 0..c.grid.size() {
         for y in 0..c.grid.size() {
             assert_eq!(cmp[y][x] == 1, c.grid.bit(y, x))
@@ -0,0 +1,52 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use rqrr::{DeQRError, Grid, SimpleGrid};
+
+/// Allocator counting the allocations of the current thread
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.with(|count| count.set(count.get() + 1));
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOC: CountingAlloc = CountingAlloc;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|count| count.get())
+}
+
+#[test]
+fn test_decode_with_scratch_does_not_allocate() {
+    let grid = Grid::new(SimpleGrid::encode(b"https://github.com/WanzenBug/rqrr", 2).unwrap());
+    let mut scratch = [0; rqrr::MAX_SCRATCH_SIZE];
+    let mut content = [0; 64];
+
+    let before = allocations();
+    let meta = grid
+        .decode_with_scratch(&mut scratch, &mut content[..])
+        .unwrap();
+    let small = grid.decode_with_scratch(&mut scratch[..10], &mut content[..]);
+    let after = allocations();
+
+    assert_eq!(before, after);
+    assert_eq!(meta.ecc_level, 2);
+    assert_eq!(&content[..33], b"https://github.com/WanzenBug/rqrr");
+    assert_eq!(small.unwrap_err(), DeQRError::BufferTooSmall);
+
+    // Regular decoding allocates, and is counted
+    grid.decode().unwrap();
+    assert!(allocations() > after);
+}